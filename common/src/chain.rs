@@ -0,0 +1,313 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, str::FromStr};
+use thiserror::Error;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub enum Chain {
+    Ethereum,
+    Polygon,
+    Gnosis,
+    Bsc,
+    Goerli,
+    Arbitrum,
+    SolanaMain,
+    SolanaTest,
+    SolanaDev,
+    Custom(String),
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("Invalid chain key {0}")]
+pub struct ChainParseError(String);
+
+impl Chain {
+    pub const BUILTIN: [Chain; 9] = [
+        Self::Ethereum,
+        Self::Polygon,
+        Self::Gnosis,
+        Self::Bsc,
+        Self::Goerli,
+        Self::Arbitrum,
+        Self::SolanaMain,
+        Self::SolanaTest,
+        Self::SolanaDev,
+    ];
+
+    pub fn is_builtin(&self) -> bool {
+        !matches!(self, Self::Custom(_))
+    }
+}
+
+impl fmt::Debug for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let res = match self {
+            Self::Ethereum => "ethereum",
+            Self::Polygon => "polygon",
+            Self::Gnosis => "gnosis",
+            Self::Bsc => "bsc",
+            Self::Goerli => "goerli",
+            Self::Arbitrum => "arbitrum",
+            Self::SolanaMain => "solana_main",
+            Self::SolanaTest => "solana_test",
+            Self::SolanaDev => "solana_dev",
+            Self::Custom(key) => key,
+        };
+
+        write!(f, "{res}")
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        <Self as fmt::Debug>::fmt(self, f)
+    }
+}
+
+impl FromStr for Chain {
+    type Err = ChainParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(chain) = Self::BUILTIN
+            .into_iter()
+            .find(|chain| chain.to_string() == s)
+        {
+            return Ok(chain);
+        }
+
+        let is_key = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_';
+
+        if s.is_empty() || !s.chars().all(is_key) {
+            return Err(ChainParseError(s.to_string()));
+        }
+
+        Ok(Self::Custom(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for Chain {
+    type Error = ChainParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Chain> for String {
+    fn from(chain: Chain) -> Self {
+        chain.to_string()
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Chain {
+    fn schema_name() -> String {
+        "Chain".to_string()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        let mut schema = gen.subschema_for::<String>().into_object();
+        schema.string().pattern = Some("^[a-z0-9_]+$".to_string());
+        schema.into()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ChainFamily {
+    Evm,
+    Solana,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ChainInfo {
+    #[serde(default)]
+    pub chain_id: Option<u64>,
+    pub family: ChainFamily,
+    pub native_symbol: String,
+    pub native_decimals: u8,
+    #[serde(default)]
+    pub multicall: Option<String>,
+    #[serde(default)]
+    pub rpc_urls: Vec<String>,
+    #[serde(default)]
+    pub explorer: Option<String>,
+}
+
+// Multicall3 is deployed at the same address on every supported evm chain
+pub const MULTICALL3: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+impl ChainInfo {
+    fn evm(chain_id: u64, native_symbol: &str, multicall: &str, explorer: &str) -> Self {
+        Self {
+            chain_id: Some(chain_id),
+            family: ChainFamily::Evm,
+            native_symbol: native_symbol.to_string(),
+            native_decimals: 18,
+            multicall: Some(multicall.to_string()),
+            rpc_urls: vec![],
+            explorer: Some(explorer.to_string()),
+        }
+    }
+
+    fn solana(rpc_url: &str) -> Self {
+        Self {
+            chain_id: None,
+            family: ChainFamily::Solana,
+            native_symbol: "SOL".to_string(),
+            native_decimals: 9,
+            multicall: None,
+            rpc_urls: vec![rpc_url.to_string()],
+            explorer: Some("https://explorer.solana.com".to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(transparent)]
+pub struct ChainRegistry {
+    chains: HashMap<Chain, ChainInfo>,
+}
+
+impl ChainRegistry {
+    pub fn builtin() -> Self {
+        let chains = [
+            (
+                Chain::Ethereum,
+                ChainInfo::evm(1, "ETH", MULTICALL3, "https://etherscan.io"),
+            ),
+            (
+                Chain::Polygon,
+                ChainInfo::evm(137, "MATIC", MULTICALL3, "https://polygonscan.com"),
+            ),
+            (
+                Chain::Gnosis,
+                ChainInfo::evm(100, "xDAI", MULTICALL3, "https://gnosisscan.io"),
+            ),
+            (
+                Chain::Bsc,
+                ChainInfo::evm(56, "BNB", MULTICALL3, "https://bscscan.com"),
+            ),
+            (
+                Chain::Goerli,
+                ChainInfo::evm(5, "ETH", MULTICALL3, "https://goerli.etherscan.io"),
+            ),
+            (
+                Chain::Arbitrum,
+                ChainInfo::evm(42161, "ETH", MULTICALL3, "https://arbiscan.io"),
+            ),
+            (
+                Chain::SolanaMain,
+                ChainInfo::solana("https://api.mainnet-beta.solana.com"),
+            ),
+            (
+                Chain::SolanaTest,
+                ChainInfo::solana("https://api.testnet.solana.com"),
+            ),
+            (
+                Chain::SolanaDev,
+                ChainInfo::solana("https://api.devnet.solana.com"),
+            ),
+        ];
+
+        Self {
+            chains: chains.into_iter().collect(),
+        }
+    }
+
+    pub fn get(&self, chain: &Chain) -> Option<&ChainInfo> {
+        self.chains.get(chain)
+    }
+
+    pub fn find_by_chain_id(&self, chain_id: u64) -> Option<(&Chain, &ChainInfo)> {
+        self.chains
+            .iter()
+            .find(|(_, info)| info.chain_id == Some(chain_id))
+    }
+
+    pub fn register(&mut self, chain: Chain, info: ChainInfo) -> Option<ChainInfo> {
+        self.chains.insert(chain, info)
+    }
+
+    pub fn extend(&mut self, other: ChainRegistry) {
+        self.chains.extend(other.chains)
+    }
+
+    pub fn chains(&self) -> impl Iterator<Item = (&Chain, &ChainInfo)> {
+        self.chains.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Chain, ChainFamily, ChainRegistry, MULTICALL3};
+    use std::str::FromStr;
+
+    #[test]
+    fn chain_parse() {
+        for chain in Chain::BUILTIN {
+            assert_eq!(Chain::from_str(&chain.to_string()), Ok(chain));
+        }
+
+        let base = Chain::from_str("base").unwrap();
+
+        assert_eq!(base, Chain::Custom("base".to_string()));
+        assert!(!base.is_builtin());
+        assert!(Chain::from_str("Base").is_err());
+        assert!(Chain::from_str("").is_err());
+
+        assert_eq!(
+            serde_json::to_string(&Chain::SolanaMain).unwrap(),
+            r#""solana_main""#
+        );
+        assert_eq!(
+            serde_json::from_str::<Chain>(r#""optimism""#).unwrap(),
+            Chain::Custom("optimism".to_string())
+        );
+        assert!(serde_json::from_str::<Chain>(r#""not a chain""#).is_err());
+    }
+
+    #[test]
+    fn chain_registry() {
+        let mut registry = ChainRegistry::builtin();
+
+        assert_eq!(registry.get(&Chain::Ethereum).unwrap().chain_id, Some(1));
+        assert_eq!(
+            registry.get(&Chain::Arbitrum).unwrap().multicall.as_deref(),
+            Some(MULTICALL3)
+        );
+        assert_eq!(
+            registry.get(&Chain::SolanaMain).unwrap().family,
+            ChainFamily::Solana
+        );
+
+        let config = r#"{
+            "base": {
+                "chain_id": 8453,
+                "family": "evm",
+                "native_symbol": "ETH",
+                "native_decimals": 18,
+                "multicall": "0xcA11bde05977b3631167028862bE2a173976CA11",
+                "rpc_urls": ["https://mainnet.base.org"]
+            },
+            "polygon": {
+                "chain_id": 137,
+                "family": "evm",
+                "native_symbol": "POL",
+                "native_decimals": 18
+            }
+        }"#;
+
+        registry.extend(serde_json::from_str(config).unwrap());
+
+        let (base, info) = registry.find_by_chain_id(8453).unwrap();
+
+        assert_eq!(base, &Chain::Custom("base".to_string()));
+        assert_eq!(info.rpc_urls, ["https://mainnet.base.org"]);
+        assert_eq!(registry.get(&Chain::Polygon).unwrap().native_symbol, "POL");
+        assert_eq!(registry.chains().count(), Chain::BUILTIN.len() + 1);
+    }
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: impl ToString) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+pub fn join_fields(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|error| error.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Error, Debug)]
+pub enum PluginError {
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+    #[error("Invalid input: {}", join_fields(.0))]
+    InvalidInput(Vec<FieldError>),
+    #[error("Rpc error: {0}")]
+    Rpc(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl PluginError {
+    pub fn invalid(field: &str, message: impl ToString) -> Self {
+        Self::InvalidInput(vec![FieldError::new(field, message)])
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Rpc(_))
+    }
+}
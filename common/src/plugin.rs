@@ -0,0 +1,10 @@
+use crate::Scalar;
+
+// returned by a plugin's retrieve: the values of each user's identities,
+// None where a value couldn't be determined, and the block they were read
+// at if the plugin pins one
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PluginOutput {
+    pub values: Vec<Vec<Option<Scalar>>>,
+    pub block: Option<u64>,
+}
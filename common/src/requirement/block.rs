@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("{0} is not a valid block hash, expected 0x followed by 64 hex characters")]
+pub struct BlockHashError(String);
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(try_from = "String", into = "String")]
+pub struct BlockHash(String);
+
+// the block a requirement is evaluated at, timestamps are resolved to the
+// last block mined at or before them
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum BlockSpec {
+    #[default]
+    Latest,
+    Number(u64),
+    Hash(BlockHash),
+    Timestamp(u64),
+}
+
+impl BlockHash {
+    pub fn new(hash: &str) -> Result<Self, BlockHashError> {
+        let is_valid = hash
+            .strip_prefix("0x")
+            .is_some_and(|hex| hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()));
+
+        if !is_valid {
+            return Err(BlockHashError(hash.to_string()));
+        }
+
+        Ok(Self(hash.to_ascii_lowercase()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for BlockHash {
+    type Error = BlockHashError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(&value)
+    }
+}
+
+impl From<BlockHash> for String {
+    fn from(value: BlockHash) -> Self {
+        value.0
+    }
+}
+
+impl fmt::Display for BlockHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
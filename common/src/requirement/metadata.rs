@@ -0,0 +1,380 @@
+use super::{BlockSpec, ContractAddress, TokenType};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{fmt, str::FromStr};
+
+// evm_balance metadata is a serialized TokenType, optionally pinned to a
+// block with an additional "block" key, e.g.
+// {"Erc20":{"address":"0x..."},"block":{"Number":17000000}}
+// without it the block of the provider is used
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvmBalanceMetadata {
+    pub token: TokenType,
+    pub block: Option<BlockSpec>,
+}
+
+impl From<TokenType> for EvmBalanceMetadata {
+    fn from(token: TokenType) -> Self {
+        Self { token, block: None }
+    }
+}
+
+impl FromStr for EvmBalanceMetadata {
+    type Err = serde_json::Error;
+
+    fn from_str(metadata: &str) -> Result<Self, Self::Err> {
+        let mut value: Value = serde_json::from_str(metadata)?;
+
+        let block = value
+            .as_object_mut()
+            .and_then(|map| map.remove("block"))
+            .map(serde_json::from_value)
+            .transpose()?;
+        let token = serde_json::from_value(value)?;
+
+        Ok(Self { token, block })
+    }
+}
+
+impl fmt::Display for EvmBalanceMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut value = serde_json::to_value(&self.token).map_err(|_| fmt::Error)?;
+
+        if let Some(block) = &self.block {
+            // unit variants like Native serialize to a plain string
+            if let Value::String(variant) = value {
+                value = json!({ variant: null });
+            }
+
+            if let Some(map) = value.as_object_mut() {
+                let block = serde_json::to_value(block).map_err(|_| fmt::Error)?;
+                map.insert("block".to_string(), block);
+            }
+        }
+
+        write!(f, "{value}")
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for EvmBalanceMetadata {
+    fn schema_name() -> String {
+        "EvmBalanceMetadata".to_string()
+    }
+
+    // the variants of TokenType with an optional block next to them, unit
+    // variants take the {"Native":null} form to hold one
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        use schemars::schema::{InstanceType, Schema, SchemaObject};
+
+        let block = gen.subschema_for::<BlockSpec>();
+        let mut schema = TokenType::json_schema(gen).into_object();
+        let mut variants = vec![];
+
+        for variant in schema.subschemas().one_of.take().unwrap_or_default() {
+            let mut variant = variant.into_object();
+
+            if let Some(units) = variant.enum_values.clone() {
+                variants.push(variant.into());
+
+                for unit in units.iter().filter_map(Value::as_str) {
+                    let mut object = SchemaObject {
+                        instance_type: Some(InstanceType::Object.into()),
+                        ..Default::default()
+                    };
+                    let validation = object.object();
+                    validation.required.insert(unit.to_string());
+                    validation
+                        .properties
+                        .insert(unit.to_string(), gen.subschema_for::<()>());
+                    validation
+                        .properties
+                        .insert("block".to_string(), block.clone());
+                    validation.additional_properties = Some(Box::new(Schema::Bool(false)));
+
+                    variants.push(object.into());
+                }
+            } else {
+                variant
+                    .object()
+                    .properties
+                    .insert("block".to_string(), block.clone());
+                variants.push(variant.into());
+            }
+        }
+
+        schema.subschemas().one_of = Some(variants);
+        schema.into()
+    }
+}
+
+// how the return value of an evm_call function is turned into a Scalar, bools
+// become 0 or 1
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ReturnType {
+    Uint,
+    Int,
+    Bool,
+}
+
+// evm_call metadata: a view function called for every address, where each
+// "{user}" in the arguments is replaced with the address, e.g.
+// {"address":"0x...","signature":"balanceOfAt(address,uint256)",
+// "args":["{user}","17000000"],"returns":"Uint","decimals":18}
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct EvmCallMetadata {
+    pub address: ContractAddress,
+    pub signature: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub returns: ReturnType,
+    #[serde(default)]
+    pub decimals: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block: Option<BlockSpec>,
+}
+
+impl FromStr for EvmCallMetadata {
+    type Err = serde_json::Error;
+
+    fn from_str(metadata: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(metadata)
+    }
+}
+
+impl fmt::Display for EvmCallMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metadata = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+
+        write!(f, "{metadata}")
+    }
+}
+
+// the per-address value an evm_logs requirement tests, computed from the
+// logs the contract emitted since `from_block`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum LogMetric {
+    // the first block the address received tokens in, no value if it never did
+    FirstAcquired,
+    // seconds since the address has been holding tokens without interruption
+    HoldingDuration,
+    // number of Transfer events sending tokens from or to the address
+    TransferCount,
+    // number of events of the contract with the address as an indexed argument,
+    // calls that emit no such event (or only name the address in unindexed
+    // data) are not counted
+    InteractionCount,
+}
+
+// evm_logs metadata, e.g.
+// {"address":"0x...","metric":"HoldingDuration","from_block":12287507}
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct EvmLogsMetadata {
+    pub address: ContractAddress,
+    pub metric: LogMetric,
+    #[serde(default)]
+    pub from_block: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block: Option<BlockSpec>,
+}
+
+impl FromStr for EvmLogsMetadata {
+    type Err = serde_json::Error;
+
+    fn from_str(metadata: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(metadata)
+    }
+}
+
+impl fmt::Display for EvmLogsMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metadata = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+
+        write!(f, "{metadata}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EvmBalanceMetadata, EvmCallMetadata, EvmLogsMetadata, LogMetric, ReturnType};
+    use crate::{BlockHash, BlockSpec, ContractAddress, TokenType};
+
+    #[test]
+    fn evm_balance_metadata() {
+        let token = TokenType::Erc20 {
+            address: ContractAddress::new("0x458691c1692cd82facfb2c5127e36d63213448a8").unwrap(),
+        };
+        let latest = EvmBalanceMetadata::from(token.clone());
+
+        assert_eq!(
+            latest.to_string(),
+            r#"{"Erc20":{"address":"0x458691c1692cd82facfb2c5127e36d63213448a8"}}"#
+        );
+        assert_eq!(
+            latest.to_string().parse::<EvmBalanceMetadata>().unwrap(),
+            latest
+        );
+
+        let pinned = EvmBalanceMetadata {
+            token,
+            block: Some(BlockSpec::Number(17_000_000)),
+        };
+
+        assert_eq!(
+            pinned.to_string(),
+            r#"{"Erc20":{"address":"0x458691c1692cd82facfb2c5127e36d63213448a8"},"block":{"Number":17000000}}"#
+        );
+        assert_eq!(
+            pinned.to_string().parse::<EvmBalanceMetadata>().unwrap(),
+            pinned
+        );
+
+        let native = EvmBalanceMetadata {
+            token: TokenType::Native,
+            block: Some(BlockSpec::Timestamp(1_681_338_455)),
+        };
+
+        assert_eq!(
+            native.to_string(),
+            r#"{"Native":null,"block":{"Timestamp":1681338455}}"#
+        );
+        assert_eq!(
+            native.to_string().parse::<EvmBalanceMetadata>().unwrap(),
+            native
+        );
+        assert_eq!(
+            r#""Native""#.parse::<EvmBalanceMetadata>().unwrap(),
+            EvmBalanceMetadata::from(TokenType::Native)
+        );
+
+        // an explicit latest block overrides the block of the provider
+        let latest = r#"{"Native":null,"block":"Latest"}"#;
+        let metadata = latest.parse::<EvmBalanceMetadata>().unwrap();
+
+        assert_eq!(metadata.block, Some(BlockSpec::Latest));
+        assert_eq!(metadata.to_string(), latest);
+
+        let hash = "0x2A6D3A5A1F6B1B8B3D9B2B4E0F0F1A8C1C2B3D4E5F60718293A4B5C6D7E8F901";
+        let by_hash: EvmBalanceMetadata =
+            format!(r#"{{"Native":null,"block":{{"Hash":"{hash}"}}}}"#)
+                .parse()
+                .unwrap();
+
+        assert_eq!(
+            by_hash.block,
+            Some(BlockSpec::Hash(
+                BlockHash::new(&hash.to_ascii_lowercase()).unwrap()
+            ))
+        );
+
+        let invalid = [
+            r#"{"Native":null,"block":{"Hash":"0x2a6d"}}"#,
+            r#"{"Native":null,"block":{"Number":-1}}"#,
+            r#"{"Native":null,"block":"Earliest"}"#,
+            r#"{"block":"Latest"}"#,
+        ];
+
+        for metadata in invalid {
+            assert!(metadata.parse::<EvmBalanceMetadata>().is_err());
+        }
+    }
+
+    #[test]
+    fn evm_call_metadata() {
+        let metadata: EvmCallMetadata = r#"{
+            "address": "0x9c4b82c5b7e50b4d6ac7bd9e0fda2ebd2c00b2e3",
+            "signature": "isMember(address)",
+            "args": ["{user}"],
+            "returns": "Bool"
+        }"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            metadata,
+            EvmCallMetadata {
+                address: ContractAddress::new("0x9c4b82c5b7e50b4d6ac7bd9e0fda2ebd2c00b2e3")
+                    .unwrap(),
+                signature: "isMember(address)".to_string(),
+                args: vec!["{user}".to_string()],
+                returns: ReturnType::Bool,
+                decimals: 0,
+                block: None,
+            }
+        );
+        assert_eq!(
+            metadata.to_string(),
+            r#"{"address":"0x9c4b82c5b7e50b4d6ac7bd9e0fda2ebd2c00b2e3","signature":"isMember(address)","args":["{user}"],"returns":"Bool","decimals":0}"#
+        );
+
+        let pinned = EvmCallMetadata {
+            signature: "balanceOfAt(address,uint256)".to_string(),
+            args: vec!["{user}".to_string(), "17000000".to_string()],
+            returns: ReturnType::Uint,
+            decimals: 18,
+            block: Some(BlockSpec::Number(17_000_000)),
+            ..metadata
+        };
+
+        assert_eq!(
+            pinned.to_string().parse::<EvmCallMetadata>().unwrap(),
+            pinned
+        );
+
+        let invalid = [
+            r#"{"address":"0x9c4b","signature":"isMember(address)","returns":"Bool"}"#,
+            r#"{"address":"0x9c4b82c5b7e50b4d6ac7bd9e0fda2ebd2c00b2e3","signature":"f()","returns":"String"}"#,
+            r#"{"address":"0x9c4b82c5b7e50b4d6ac7bd9e0fda2ebd2c00b2e3","returns":"Uint"}"#,
+        ];
+
+        for metadata in invalid {
+            assert!(metadata.parse::<EvmCallMetadata>().is_err());
+        }
+    }
+
+    #[test]
+    fn evm_logs_metadata() {
+        let metadata: EvmLogsMetadata = r#"{
+            "address": "0x57f1887a8bf19b14fc0df6fd9b2acc9af147ea85",
+            "metric": "HoldingDuration",
+            "from_block": 9380410
+        }"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            metadata,
+            EvmLogsMetadata {
+                address: ContractAddress::new("0x57f1887a8bf19b14fc0df6fd9b2acc9af147ea85")
+                    .unwrap(),
+                metric: LogMetric::HoldingDuration,
+                from_block: 9_380_410,
+                block: None,
+            }
+        );
+        assert_eq!(
+            metadata.to_string(),
+            r#"{"address":"0x57f1887a8bf19b14fc0df6fd9b2acc9af147ea85","metric":"HoldingDuration","from_block":9380410}"#
+        );
+
+        let pinned = EvmLogsMetadata {
+            metric: LogMetric::InteractionCount,
+            block: Some(BlockSpec::Timestamp(1_681_338_455)),
+            ..metadata
+        };
+
+        assert_eq!(
+            pinned.to_string().parse::<EvmLogsMetadata>().unwrap(),
+            pinned
+        );
+        assert!(
+            r#"{"address":"0x57f1887a8bf19b14fc0df6fd9b2acc9af147ea85","metric":"Balance"}"#
+                .parse::<EvmLogsMetadata>()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use bigdecimal::{num_bigint::BigInt, BigDecimal};
+use core::ops::{Range, RangeInclusive};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+pub use block::*;
+pub use metadata::{EvmBalanceMetadata, EvmCallMetadata, EvmLogsMetadata, LogMetric, ReturnType};
+pub use parse::RelationParseError;
+pub use token::*;
+
+mod block;
+mod metadata;
+mod parse;
+mod token;
+
+pub type Scalar = BigDecimal;
+
+pub fn scalar_from_units(units: &str, decimals: u32) -> Option<Scalar> {
+    BigInt::from_str(units)
+        .ok()
+        .map(|units| BigDecimal::new(units, decimals.into()))
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum RequirementType {
+    EvmBalance,
+    EvmCall,
+    EvmLogs,
+    SolBalance,
+}
+
+impl fmt::Debug for RequirementType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let res = match self {
+            Self::EvmBalance => "evm_balance",
+            Self::EvmCall => "evm_call",
+            Self::EvmLogs => "evm_logs",
+            Self::SolBalance => "sol_balance",
+        };
+
+        write!(f, "{res}")
+    }
+}
+
+impl fmt::Display for RequirementType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        <Self as fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("Unknown requirement type {0}")]
+pub struct RequirementTypeParseError(String);
+
+impl FromStr for RequirementType {
+    type Err = RequirementTypeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "evm_balance" | "EvmBalance" => Ok(Self::EvmBalance),
+            "evm_call" | "EvmCall" => Ok(Self::EvmCall),
+            "evm_logs" | "EvmLogs" => Ok(Self::EvmLogs),
+            "sol_balance" | "SolBalance" => Ok(Self::SolBalance),
+            _ => Err(RequirementTypeParseError(s.to_string())),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Relation<T> {
+    EqualTo(T),
+    NotEqualTo(T),
+    GreaterThan(T),
+    GreaterOrEqualTo(T),
+    LessThan(T),
+    LessOrEqualTo(T),
+    Between(Range<T>),
+    BetweenInclusive(RangeInclusive<T>),
+    In(Vec<T>),
+    NotIn(Vec<T>),
+    Not(Box<Relation<T>>),
+    All(Vec<Relation<T>>),
+    Any(Vec<Relation<T>>),
+}
+
+impl<T> Relation<T>
+where
+    T: PartialEq + PartialOrd,
+{
+    pub fn assert(&self, x: &T) -> bool {
+        match self {
+            Relation::EqualTo(a) => x == a,
+            Relation::NotEqualTo(a) => x != a,
+            Relation::GreaterThan(a) => x > a,
+            Relation::GreaterOrEqualTo(a) => x >= a,
+            Relation::LessThan(a) => x < a,
+            Relation::LessOrEqualTo(a) => x <= a,
+            Relation::Between(range) => range.contains(x),
+            Relation::BetweenInclusive(range) => range.contains(x),
+            Relation::In(set) => set.contains(x),
+            Relation::NotIn(set) => !set.contains(x),
+            Relation::Not(relation) => !relation.assert(x),
+            Relation::All(relations) => relations.iter().all(|relation| relation.assert(x)),
+            Relation::Any(relations) => relations.iter().any(|relation| relation.assert(x)),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Aggregation {
+    #[default]
+    Any,
+    All,
+    Sum,
+    Max,
+    Min,
+    Count(Relation<Scalar>),
+}
+
+impl Aggregation {
+    pub fn assert(&self, relation: &Relation<Scalar>, values: &[Scalar]) -> bool {
+        match self {
+            Aggregation::Any => values.iter().any(|v| relation.assert(v)),
+            Aggregation::All => !values.is_empty() && values.iter().all(|v| relation.assert(v)),
+            Aggregation::Sum => !values.is_empty() && relation.assert(&values.iter().sum()),
+            Aggregation::Max => values.iter().max().is_some_and(|v| relation.assert(v)),
+            Aggregation::Min => values.iter().min().is_some_and(|v| relation.assert(v)),
+            Aggregation::Count(_) if values.is_empty() => false,
+            Aggregation::Count(matching) => {
+                let count = values.iter().filter(|v| matching.assert(v)).count();
+                relation.assert(&Scalar::from(count as u64))
+            }
+        }
+    }
+
+    // unknown values (e.g. failed calls) can't be aggregated, only a known
+    // value satisfying an Any aggregation decides the outcome without them
+    pub fn assert_partial(&self, relation: &Relation<Scalar>, values: &[Option<Scalar>]) -> bool {
+        let known = values.iter().flatten().cloned().collect::<Vec<Scalar>>();
+
+        if known.len() == values.len() || self == &Aggregation::Any {
+            self.assert(relation, &known)
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum IdentityScope {
+    #[default]
+    All,
+    Primary,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{scalar_from_units, Aggregation, Relation, RequirementType, Scalar};
+    use shiba as _;
+    use std::str::FromStr;
+
+    #[test]
+    fn relations() {
+        assert!(Relation::EqualTo(0.0).assert(&0.0));
+        assert!(!Relation::EqualTo(10.0).assert(&2.0));
+        assert!(!Relation::EqualTo(420.0).assert(&421.0));
+
+        assert!(!Relation::GreaterThan(10.0).assert(&3.0));
+        assert!(!Relation::GreaterThan(10.0).assert(&10.0));
+        assert!(Relation::GreaterThan(10.0).assert(&20.0));
+
+        assert!(Relation::GreaterOrEqualTo(23.0).assert(&42.0));
+        assert!(Relation::GreaterOrEqualTo(23.0).assert(&23.0));
+        assert!(!Relation::GreaterOrEqualTo(23.0).assert(&14.0));
+
+        assert!(Relation::LessThan(23.0).assert(&1.0));
+        assert!(!Relation::LessThan(23.0).assert(&23.0));
+        assert!(!Relation::LessThan(23.0).assert(&42.0));
+
+        assert!(Relation::LessOrEqualTo(23.0).assert(&1.0));
+        assert!(Relation::LessOrEqualTo(23.0).assert(&23.0));
+        assert!(!Relation::LessOrEqualTo(23.0).assert(&42.0));
+
+        assert!(!Relation::Between(0.0..100.0).assert(&230.0));
+        assert!(!Relation::Between(50.0..100.0).assert(&15.0));
+        assert!(!Relation::Between(50.0..100.0).assert(&100.0));
+        assert!(Relation::Between(50.0..100.0).assert(&77.0));
+        assert!(Relation::Between(50.0..100.0).assert(&50.0));
+
+        assert!(!Relation::BetweenInclusive(0.0..=100.0).assert(&230.0));
+        assert!(!Relation::BetweenInclusive(50.0..=100.0).assert(&15.0));
+        assert!(Relation::BetweenInclusive(50.0..=100.0).assert(&100.0));
+        assert!(Relation::BetweenInclusive(50.0..=100.0).assert(&77.0));
+        assert!(Relation::BetweenInclusive(50.0..=100.0).assert(&50.0));
+
+        assert!(Relation::NotEqualTo(0.0).assert(&1.0));
+        assert!(!Relation::NotEqualTo(1000.0).assert(&1000.0));
+
+        assert!(Relation::In(vec![3.0, 7.0, 12.0]).assert(&7.0));
+        assert!(!Relation::In(vec![3.0, 7.0, 12.0]).assert(&8.0));
+        assert!(!Relation::In(vec![]).assert(&8.0));
+
+        assert!(Relation::NotIn(vec![3.0, 7.0, 12.0]).assert(&8.0));
+        assert!(!Relation::NotIn(vec![3.0, 7.0, 12.0]).assert(&12.0));
+    }
+
+    #[test]
+    fn relation_combinators() {
+        let not_between = Relation::Not(Box::new(Relation::Between(50.0..100.0)));
+
+        assert!(not_between.assert(&15.0));
+        assert!(not_between.assert(&100.0));
+        assert!(!not_between.assert(&50.0));
+
+        let non_dev = Relation::All(vec![
+            Relation::NotEqualTo(0.0),
+            Relation::NotEqualTo(1000.0),
+        ]);
+
+        assert!(non_dev.assert(&1.0));
+        assert!(!non_dev.assert(&0.0));
+        assert!(!non_dev.assert(&1000.0));
+        assert!(Relation::All(vec![]).assert(&1.0));
+
+        let either = Relation::Any(vec![
+            Relation::LessThan(10.0),
+            Relation::In(vec![20.0, 30.0]),
+        ]);
+
+        assert!(either.assert(&5.0));
+        assert!(either.assert(&30.0));
+        assert!(!either.assert(&25.0));
+        assert!(!Relation::Any(vec![]).assert(&1.0));
+
+        let json = r#"{"Not":{"Any":[{"In":[3,7,12]},{"NotEqualTo":0}]}}"#;
+        let relation: Relation<u64> = serde_json::from_str(json).unwrap();
+
+        assert_eq!(
+            relation,
+            Relation::Not(Box::new(Relation::Any(vec![
+                Relation::In(vec![3, 7, 12]),
+                Relation::NotEqualTo(0)
+            ])))
+        );
+        assert_eq!(serde_json::to_string(&relation).unwrap(), json);
+    }
+
+    #[test]
+    fn exact_scalars() {
+        let threshold = Scalar::from_str("1000.000000000000000001").unwrap();
+        let balance = scalar_from_units("1000000000000000000001", 18).unwrap();
+        let just_below = scalar_from_units("1000000000000000000000", 18).unwrap();
+
+        assert!(Relation::GreaterOrEqualTo(threshold.clone()).assert(&balance));
+        assert!(!Relation::GreaterOrEqualTo(threshold).assert(&just_below));
+
+        let one_token = scalar_from_units("1000000000000000000", 18).unwrap();
+        assert!(Relation::EqualTo(Scalar::from(1)).assert(&one_token));
+
+        let max_uint256 = scalar_from_units(
+            "115792089237316195423570985008687907853269984665640564039457584007913129639935",
+            18,
+        )
+        .unwrap();
+        assert!(Relation::GreaterThan(Scalar::from(u64::MAX)).assert(&max_uint256));
+
+        assert!(scalar_from_units("0x1f", 0).is_none());
+    }
+
+    #[test]
+    fn aggregations() {
+        let wallets = ["0.6", "0.6", "0"]
+            .iter()
+            .map(|value| Scalar::from_str(value).unwrap())
+            .collect::<Vec<_>>();
+        let at_least_one = Relation::GreaterOrEqualTo(Scalar::from(1));
+
+        assert!(!Aggregation::Any.assert(&at_least_one, &wallets));
+        assert!(Aggregation::Sum.assert(&at_least_one, &wallets));
+        assert!(!Aggregation::Max.assert(&at_least_one, &wallets));
+
+        let holds_some = Relation::GreaterThan(Scalar::from(0));
+
+        assert!(Aggregation::Any.assert(&holds_some, &wallets));
+        assert!(!Aggregation::All.assert(&holds_some, &wallets));
+        assert!(Aggregation::All.assert(&holds_some, &wallets[..2]));
+        assert!(!Aggregation::Min.assert(&holds_some, &wallets));
+
+        let two_holding_wallets = Aggregation::Count(holds_some.clone());
+
+        assert!(two_holding_wallets.assert(&Relation::EqualTo(Scalar::from(2)), &wallets));
+        assert!(!two_holding_wallets.assert(&Relation::GreaterThan(Scalar::from(2)), &wallets));
+
+        assert!(!Aggregation::Any.assert(&holds_some, &[]));
+        assert!(!Aggregation::All.assert(&holds_some, &[]));
+        assert!(!Aggregation::Max.assert(&holds_some, &[]));
+        assert!(!Aggregation::Sum.assert(&holds_some, &[]));
+        assert!(!Aggregation::Sum.assert(&Relation::LessThan(Scalar::from(1)), &[]));
+        assert!(!two_holding_wallets.assert(&Relation::EqualTo(Scalar::from(0)), &[]));
+        assert_eq!(Aggregation::default(), Aggregation::Any);
+
+        let partial = [Some(Scalar::from(2)), None];
+
+        assert!(Aggregation::Any.assert_partial(&at_least_one, &partial));
+        assert!(!Aggregation::Any.assert_partial(&holds_some, &[None]));
+        assert!(!Aggregation::Sum.assert_partial(&at_least_one, &partial));
+        assert!(!Aggregation::Min.assert_partial(&Relation::LessThan(Scalar::from(1)), &partial));
+        assert!(Aggregation::Sum.assert_partial(&at_least_one, &partial[..1]));
+    }
+
+    #[test]
+    fn requirement_type_parse() {
+        for typ in [
+            RequirementType::EvmBalance,
+            RequirementType::EvmCall,
+            RequirementType::EvmLogs,
+            RequirementType::SolBalance,
+        ] {
+            let serialized = serde_json::to_string(&typ).unwrap();
+
+            assert_eq!(RequirementType::from_str(&typ.to_string()), Ok(typ));
+            assert_eq!(
+                RequirementType::from_str(serialized.trim_matches('"')),
+                Ok(typ)
+            );
+        }
+
+        assert_eq!(
+            RequirementType::from_str("evmBalance")
+                .unwrap_err()
+                .to_string(),
+            "Unknown requirement type evmBalance"
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use super::Relation;
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RelationParseError {
+    #[error("Unexpected end of input")]
+    UnexpectedEnd,
+    #[error("Unexpected input at position {0}: {1}")]
+    Unexpected(usize, String),
+    #[error("Invalid value {0}")]
+    InvalidValue(String),
+}
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{item}")?;
+    }
+
+    Ok(())
+}
+
+impl<T: fmt::Display> fmt::Display for Relation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EqualTo(a) => write!(f, "== {a}"),
+            Self::NotEqualTo(a) => write!(f, "!= {a}"),
+            Self::GreaterThan(a) => write!(f, "> {a}"),
+            Self::GreaterOrEqualTo(a) => write!(f, ">= {a}"),
+            Self::LessThan(a) => write!(f, "< {a}"),
+            Self::LessOrEqualTo(a) => write!(f, "<= {a}"),
+            Self::Between(range) => write!(f, "in [{}, {})", range.start, range.end),
+            Self::BetweenInclusive(range) => {
+                write!(f, "in [{}, {}]", range.start(), range.end())
+            }
+            Self::In(set) => {
+                write!(f, "in {{")?;
+                write_list(f, set)?;
+                write!(f, "}}")
+            }
+            Self::NotIn(set) => {
+                write!(f, "not in {{")?;
+                write_list(f, set)?;
+                write!(f, "}}")
+            }
+            Self::Not(relation) => write!(f, "not ({relation})"),
+            Self::All(relations) => {
+                write!(f, "all(")?;
+                write_list(f, relations)?;
+                write!(f, ")")
+            }
+            Self::Any(relations) => {
+                write!(f, "any(")?;
+                write_list(f, relations)?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.pos = self.input.len() - self.rest().trim_start().len();
+    }
+
+    fn unexpected(&self) -> RelationParseError {
+        let rest = self.rest().trim();
+
+        if rest.is_empty() {
+            RelationParseError::UnexpectedEnd
+        } else {
+            RelationParseError::Unexpected(self.pos, rest.to_string())
+        }
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+
+        let is_keyword = self
+            .rest()
+            .strip_prefix(keyword)
+            .is_some_and(|after| !after.starts_with(|c: char| c.is_alphanumeric() || c == '_'));
+
+        if is_keyword {
+            self.pos += keyword.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), RelationParseError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn value<T: FromStr>(&mut self) -> Result<T, RelationParseError> {
+        self.skip_whitespace();
+
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || ",()[]{}".contains(c))
+            .unwrap_or(rest.len());
+
+        if len == 0 {
+            return Err(self.unexpected());
+        }
+
+        let raw = &rest[..len];
+        let value = raw
+            .parse()
+            .map_err(|_| RelationParseError::InvalidValue(raw.to_string()))?;
+        self.pos += len;
+
+        Ok(value)
+    }
+
+    fn list<U>(
+        &mut self,
+        close: &str,
+        mut item: impl FnMut(&mut Self) -> Result<U, RelationParseError>,
+    ) -> Result<Vec<U>, RelationParseError> {
+        let mut items = vec![];
+
+        if self.eat(close) {
+            return Ok(items);
+        }
+
+        loop {
+            items.push(item(self)?);
+
+            if self.eat(close) {
+                return Ok(items);
+            }
+
+            self.expect(",")?;
+        }
+    }
+
+    fn membership<T: FromStr>(&mut self) -> Result<Relation<T>, RelationParseError> {
+        if self.eat("{") {
+            return Ok(Relation::In(self.list("}", Self::value)?));
+        }
+
+        self.expect("[")?;
+        let start = self.value()?;
+        self.expect(",")?;
+        let end = self.value()?;
+
+        if self.eat(")") {
+            return Ok(Relation::Between(start..end));
+        }
+
+        self.expect("]")?;
+
+        Ok(Relation::BetweenInclusive(start..=end))
+    }
+
+    fn relation<T: FromStr>(&mut self) -> Result<Relation<T>, RelationParseError> {
+        if self.eat("(") {
+            let relation = self.relation()?;
+            self.expect(")")?;
+
+            return Ok(relation);
+        }
+
+        if self.eat_keyword("not") {
+            if self.eat_keyword("in") {
+                return match self.membership()? {
+                    Relation::In(set) => Ok(Relation::NotIn(set)),
+                    range => Ok(Relation::Not(Box::new(range))),
+                };
+            }
+
+            return Ok(Relation::Not(Box::new(self.relation()?)));
+        }
+
+        if self.eat_keyword("in") {
+            return self.membership();
+        }
+
+        if self.eat_keyword("all") {
+            self.expect("(")?;
+            return Ok(Relation::All(self.list(")", Self::relation)?));
+        }
+
+        if self.eat_keyword("any") {
+            self.expect("(")?;
+            return Ok(Relation::Any(self.list(")", Self::relation)?));
+        }
+
+        let relation: fn(T) -> Relation<T> = if self.eat("==") {
+            Relation::EqualTo
+        } else if self.eat("!=") {
+            Relation::NotEqualTo
+        } else if self.eat(">=") {
+            Relation::GreaterOrEqualTo
+        } else if self.eat(">") {
+            Relation::GreaterThan
+        } else if self.eat("<=") {
+            Relation::LessOrEqualTo
+        } else if self.eat("<") {
+            Relation::LessThan
+        } else {
+            return Err(self.unexpected());
+        };
+
+        Ok(relation(self.value()?))
+    }
+}
+
+impl<T: FromStr> FromStr for Relation<T> {
+    type Err = RelationParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { input: s, pos: 0 };
+        let relation = parser.relation()?;
+
+        parser.skip_whitespace();
+
+        if parser.pos < s.len() {
+            return Err(parser.unexpected());
+        }
+
+        Ok(relation)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Relation, RelationParseError};
+    use crate::Scalar;
+    use std::str::FromStr;
+
+    #[test]
+    fn relation_display() {
+        assert_eq!(Relation::GreaterOrEqualTo(23.0).to_string(), ">= 23");
+        assert_eq!(Relation::NotEqualTo(0).to_string(), "!= 0");
+        assert_eq!(Relation::Between(50..100).to_string(), "in [50, 100)");
+        assert_eq!(
+            Relation::BetweenInclusive(50..=100).to_string(),
+            "in [50, 100]"
+        );
+        assert_eq!(Relation::In(vec![3, 7, 12]).to_string(), "in {3, 7, 12}");
+        assert_eq!(Relation::NotIn(vec![1000]).to_string(), "not in {1000}");
+        assert_eq!(
+            Relation::Not(Box::new(Relation::Between(50..100))).to_string(),
+            "not (in [50, 100))"
+        );
+        assert_eq!(
+            Relation::All(vec![Relation::GreaterThan(0), Relation::NotEqualTo(1000)]).to_string(),
+            "all(> 0, != 1000)"
+        );
+    }
+
+    #[test]
+    fn relation_parse() {
+        assert_eq!(">= 23".parse(), Ok(Relation::GreaterOrEqualTo(23)));
+        assert_eq!(">=23".parse(), Ok(Relation::GreaterOrEqualTo(23)));
+        assert_eq!("> 23".parse(), Ok(Relation::GreaterThan(23)));
+        assert_eq!("<= 23".parse(), Ok(Relation::LessOrEqualTo(23)));
+        assert_eq!("< 23".parse(), Ok(Relation::LessThan(23)));
+        assert_eq!("== 23".parse(), Ok(Relation::EqualTo(23)));
+        assert_eq!("in [50, 100)".parse(), Ok(Relation::Between(50..100)));
+        assert_eq!(
+            "in[50,100]".parse(),
+            Ok(Relation::BetweenInclusive(50..=100))
+        );
+        assert_eq!("in {}".parse(), Ok(Relation::<u32>::In(vec![])));
+        assert_eq!(
+            "not in {3, 7, 12}".parse(),
+            Ok(Relation::NotIn(vec![3, 7, 12]))
+        );
+        assert_eq!(
+            "not in [50, 100]".parse(),
+            Ok(Relation::Not(Box::new(Relation::BetweenInclusive(
+                50..=100
+            ))))
+        );
+        assert_eq!(
+            "any(< 10, all(!= 0, (in {20, 30})))".parse(),
+            Ok(Relation::Any(vec![
+                Relation::LessThan(10),
+                Relation::All(vec![Relation::NotEqualTo(0), Relation::In(vec![20, 30])])
+            ]))
+        );
+
+        let relation: Relation<Scalar> = ">= 1000.000000000000000001".parse().unwrap();
+        assert_eq!(
+            relation,
+            Relation::GreaterOrEqualTo(Scalar::from_str("1000.000000000000000001").unwrap())
+        );
+
+        let relations = [
+            Relation::Not(Box::new(Relation::Between(50..100))),
+            Relation::Any(vec![
+                Relation::NotIn(vec![1, 2]),
+                Relation::LessOrEqualTo(7),
+            ]),
+            Relation::All(vec![]),
+        ];
+
+        for relation in relations {
+            assert_eq!(relation.to_string().parse(), Ok(relation));
+        }
+    }
+
+    #[test]
+    fn relation_parse_errors() {
+        assert_eq!(
+            Relation::<u32>::from_str(""),
+            Err(RelationParseError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Relation::<u32>::from_str(">= x"),
+            Err(RelationParseError::InvalidValue("x".to_string()))
+        );
+        assert_eq!(
+            Relation::<u32>::from_str("in [1, 2"),
+            Err(RelationParseError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Relation::<u32>::from_str("> 1 2"),
+            Err(RelationParseError::Unexpected(4, "2".to_string()))
+        );
+        assert_eq!(
+            Relation::<u32>::from_str("inside {1}"),
+            Err(RelationParseError::Unexpected(0, "inside {1}".to_string()))
+        );
+
+        for input in ["n€", "€", "not€ 1", "in [1, €]"] {
+            assert!(Relation::<u32>::from_str(input).is_err());
+        }
+    }
+}
@@ -0,0 +1,465 @@
+use super::RequirementType;
+use bigdecimal::num_bigint::BigInt;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::{collections::HashSet, fmt, str::FromStr};
+use thiserror::Error;
+
+const U256_MAX: &str =
+    "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+
+// every id is a separate ownerOf call
+pub const MAX_TOKEN_IDS: usize = 10_000;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TokenTypeError {
+    #[error("{0} is not a valid EVM address, expected 0x followed by 40 hex characters")]
+    InvalidContractAddress(String),
+    #[error("{0} is not a valid uint256 token id")]
+    InvalidTokenId(String),
+    #[error("{0} is not a valid Solana address, expected 32 base58 encoded bytes")]
+    InvalidMintAddress(String),
+    #[error("Token id lists can't be empty")]
+    EmptyTokenIds,
+    #[error("{0} token ids are given, at most {MAX_TOKEN_IDS} are allowed")]
+    TooManyTokenIds(usize),
+    #[error("Token id range {start}..={end} is invalid, expected at most {MAX_TOKEN_IDS} ids")]
+    InvalidTokenRange { start: String, end: String },
+    #[error("{standard} tokens are not supported by {typ} requirements")]
+    Unsupported {
+        standard: &'static str,
+        typ: RequirementType,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(try_from = "String", into = "String")]
+pub struct ContractAddress(String);
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(try_from = "String", into = "String")]
+pub struct TokenId(String);
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(try_from = "String", into = "String")]
+pub struct MintAddress(String);
+
+// a single id, a list of ids or an inclusive range of ids
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum TokenIds {
+    Single(TokenId),
+    List(Vec<TokenId>),
+    Range { start: TokenId, end: TokenId },
+}
+
+// whether the balances of multiple token ids are summed or returned as
+// separate values per id
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum BalanceMode {
+    #[default]
+    Sum,
+    PerId,
+}
+
+impl BalanceMode {
+    pub fn is_sum(&self) -> bool {
+        self == &Self::Sum
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum TokenType {
+    Native,
+    #[serde(alias = "Fungible")]
+    Erc20 {
+        address: ContractAddress,
+    },
+    #[serde(alias = "NonFungible")]
+    Erc721 {
+        address: ContractAddress,
+        id: Option<TokenIds>,
+    },
+    // without ids every token of the collection counts
+    #[serde(alias = "Special")]
+    Erc1155 {
+        address: ContractAddress,
+        id: Option<TokenIds>,
+        #[serde(default, skip_serializing_if = "BalanceMode::is_sum")]
+        mode: BalanceMode,
+    },
+    SplToken {
+        mint: MintAddress,
+    },
+    MetaplexNft {
+        collection: MintAddress,
+    },
+}
+
+impl ContractAddress {
+    pub fn new(address: &str) -> Result<Self, TokenTypeError> {
+        let is_valid = address
+            .strip_prefix("0x")
+            .is_some_and(|hex| hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit()));
+
+        if !is_valid {
+            return Err(TokenTypeError::InvalidContractAddress(address.to_string()));
+        }
+
+        Ok(Self(address.to_ascii_lowercase()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TokenId {
+    pub fn new(id: &str) -> Result<Self, TokenTypeError> {
+        let digits = id.trim_start_matches('0');
+        let digits = if digits.is_empty() && !id.is_empty() {
+            "0"
+        } else {
+            digits
+        };
+
+        let fits =
+            digits.len() < U256_MAX.len() || (digits.len() == U256_MAX.len() && digits <= U256_MAX);
+
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) || !fits {
+            return Err(TokenTypeError::InvalidTokenId(id.to_string()));
+        }
+
+        Ok(Self(digits.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl MintAddress {
+    pub fn new(address: &str) -> Result<Self, TokenTypeError> {
+        match bs58::decode(address).into_vec() {
+            Ok(bytes) if bytes.len() == 32 => Ok(Self(address.to_string())),
+            _ => Err(TokenTypeError::InvalidMintAddress(address.to_string())),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+macro_rules! validated_string {
+    ($name:ident) => {
+        impl TryFrom<String> for $name {
+            type Error = TokenTypeError;
+
+            fn try_from(value: String) -> Result<Self, Self::Error> {
+                Self::new(&value)
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+validated_string!(ContractAddress);
+validated_string!(TokenId);
+validated_string!(MintAddress);
+
+impl TokenId {
+    fn to_bigint(&self) -> BigInt {
+        BigInt::from_str(&self.0).unwrap_or_default()
+    }
+}
+
+impl TokenIds {
+    pub fn list(ids: Vec<TokenId>) -> Result<Self, TokenTypeError> {
+        let mut seen = HashSet::new();
+        let ids = ids
+            .into_iter()
+            .filter(|id| seen.insert(id.clone()))
+            .collect::<Vec<_>>();
+
+        if ids.is_empty() {
+            return Err(TokenTypeError::EmptyTokenIds);
+        }
+        if ids.len() > MAX_TOKEN_IDS {
+            return Err(TokenTypeError::TooManyTokenIds(ids.len()));
+        }
+
+        Ok(Self::List(ids))
+    }
+
+    pub fn range(start: TokenId, end: TokenId) -> Result<Self, TokenTypeError> {
+        let count = end.to_bigint() - start.to_bigint() + 1;
+
+        if count < BigInt::from(1) || count > BigInt::from(MAX_TOKEN_IDS) {
+            return Err(TokenTypeError::InvalidTokenRange {
+                start: start.0,
+                end: end.0,
+            });
+        }
+
+        Ok(Self::Range { start, end })
+    }
+
+    pub fn to_vec(&self) -> Vec<TokenId> {
+        match self {
+            Self::Single(id) => vec![id.clone()],
+            Self::List(ids) => ids.clone(),
+            Self::Range { start, end } => {
+                let (start, end) = (start.to_bigint(), end.to_bigint());
+                let mut ids = vec![];
+                let mut id = start;
+
+                while id <= end {
+                    ids.push(TokenId(id.to_string()));
+                    id += 1;
+                }
+
+                ids
+            }
+        }
+    }
+}
+
+impl From<TokenId> for TokenIds {
+    fn from(id: TokenId) -> Self {
+        Self::Single(id)
+    }
+}
+
+impl<'de> Deserialize<'de> for TokenIds {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawTokenIds {
+            Single(String),
+            List(Vec<String>),
+            Range { start: String, end: String },
+        }
+
+        let ids = match RawTokenIds::deserialize(deserializer)? {
+            RawTokenIds::Single(id) => TokenId::new(&id).map(Self::Single),
+            RawTokenIds::List(ids) => ids
+                .iter()
+                .map(|id| TokenId::new(id))
+                .collect::<Result<Vec<_>, _>>()
+                .and_then(Self::list),
+            RawTokenIds::Range { start, end } => {
+                TokenId::new(&start).and_then(|start| Self::range(start, TokenId::new(&end)?))
+            }
+        };
+
+        ids.map_err(de::Error::custom)
+    }
+}
+
+impl TokenType {
+    pub fn standard(&self) -> &'static str {
+        match self {
+            Self::Native => "native",
+            Self::Erc20 { .. } => "ERC-20",
+            Self::Erc721 { .. } => "ERC-721",
+            Self::Erc1155 { .. } => "ERC-1155",
+            Self::SplToken { .. } => "SPL",
+            Self::MetaplexNft { .. } => "Metaplex NFT",
+        }
+    }
+
+    pub fn check_supported(&self, typ: RequirementType) -> Result<(), TokenTypeError> {
+        let standard = self.standard();
+
+        match (typ, self) {
+            (
+                RequirementType::EvmBalance,
+                Self::Native | Self::Erc20 { .. } | Self::Erc721 { .. } | Self::Erc1155 { .. },
+            ) => Ok(()),
+            _ => Err(TokenTypeError::Unsupported { standard, typ }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        BalanceMode, ContractAddress, TokenId, TokenIds, TokenType, TokenTypeError, MAX_TOKEN_IDS,
+        U256_MAX,
+    };
+    use crate::RequirementType;
+
+    #[test]
+    fn token_type_deserialization() {
+        let legacy: TokenType = serde_json::from_str(
+            r#"{"Fungible":{"address":"0x458691c1692CD82faCfb2C5127e36D63213448A8"}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            legacy,
+            TokenType::Erc20 {
+                address: ContractAddress::new("0x458691c1692cd82facfb2c5127e36d63213448a8")
+                    .unwrap()
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&legacy).unwrap(),
+            r#"{"Erc20":{"address":"0x458691c1692cd82facfb2c5127e36d63213448a8"}}"#
+        );
+
+        let special: TokenType = serde_json::from_str(
+            r#"{"Special":{"address":"0x76BE3b62873462d2142405439777e971754E8E77","id":"10868"}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(special.standard(), "ERC-1155");
+        assert_eq!(
+            serde_json::to_string(&special).unwrap(),
+            r#"{"Erc1155":{"address":"0x76be3b62873462d2142405439777e971754e8e77","id":"10868"}}"#
+        );
+
+        let per_id: TokenType = serde_json::from_str(
+            r#"{"Erc1155":{"address":"0x76be3b62873462d2142405439777e971754e8e77","id":["1","2"],"mode":"PerId"}}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            per_id,
+            TokenType::Erc1155 {
+                id: Some(TokenIds::List(_)),
+                mode: BalanceMode::PerId,
+                ..
+            }
+        ));
+
+        let invalid = [
+            r#"{"Erc20":{"address":"0x458691c1692cd82facfb2c5127e36d63213448"}}"#,
+            r#"{"Erc721":{"address":"0x57f1887a8bf19b14fc0df6fd9b2acc9af147ea85","id":"0xff"}}"#,
+            r#"{"SplToken":{"mint":"0x57f1887a8bf19b14fc0df6fd9b2acc9af147ea85"}}"#,
+        ];
+
+        for json in invalid {
+            assert!(serde_json::from_str::<TokenType>(json).is_err());
+        }
+    }
+
+    #[test]
+    fn token_ids() {
+        assert_eq!(TokenId::new("00042").unwrap().as_str(), "42");
+        assert_eq!(TokenId::new("0").unwrap().as_str(), "0");
+        assert!(TokenId::new(U256_MAX).is_ok());
+        assert_eq!(
+            TokenId::new(&format!("{U256_MAX}0")),
+            Err(TokenTypeError::InvalidTokenId(format!("{U256_MAX}0")))
+        );
+        assert!(TokenId::new(
+            "115792089237316195423570985008687907853269984665640564039457584007913129639936"
+        )
+        .is_err());
+        assert!(TokenId::new("").is_err());
+        assert!(TokenId::new("-1").is_err());
+    }
+
+    #[test]
+    fn token_id_sets() {
+        let ids = |json: &str| serde_json::from_str::<TokenIds>(json);
+        let id = |id: &str| TokenId::new(id).unwrap();
+
+        assert_eq!(ids(r#""0042""#).unwrap(), TokenIds::Single(id("42")));
+        assert_eq!(
+            ids(r#"["3", "1", "3", "2"]"#).unwrap(),
+            TokenIds::List(vec![id("3"), id("1"), id("2")])
+        );
+
+        let range = ids(r#"{"start":"1","end":"100"}"#).unwrap();
+
+        assert_eq!(range.to_vec().len(), 100);
+        assert_eq!(range.to_vec()[99], id("100"));
+        assert_eq!(
+            serde_json::to_string(&range).unwrap(),
+            r#"{"start":"1","end":"100"}"#
+        );
+
+        let near_max = format!(
+            r#"{{"start":"{}","end":"{U256_MAX}"}}"#,
+            "115792089237316195423570985008687907853269984665640564039457584007913129639934"
+        );
+        assert_eq!(ids(&near_max).unwrap().to_vec().len(), 2);
+
+        assert!(ids("[]")
+            .unwrap_err()
+            .to_string()
+            .contains("Token id lists can't be empty"));
+        assert!(ids(r#"["1", "0x2"]"#)
+            .unwrap_err()
+            .to_string()
+            .contains("0x2 is not a valid uint256 token id"));
+        assert!(ids(r#"{"start":"10","end":"9"}"#).is_err());
+        assert!(ids(&format!(r#"{{"start":"1","end":"{}"}}"#, MAX_TOKEN_IDS + 1)).is_err());
+        assert!(ids(&format!(r#"{{"start":"1","end":"{MAX_TOKEN_IDS}"}}"#)).is_ok());
+        assert_eq!(
+            TokenIds::list((0..=MAX_TOKEN_IDS).map(|i| id(&i.to_string())).collect()),
+            Err(TokenTypeError::TooManyTokenIds(MAX_TOKEN_IDS + 1))
+        );
+
+        let token: TokenType = serde_json::from_str(
+            r#"{"Erc721":{"address":"0x57f1887a8bf19b14fc0df6fd9b2acc9af147ea85","id":["1","2"]}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            token,
+            TokenType::Erc721 {
+                address: ContractAddress::new("0x57f1887a8bf19b14fc0df6fd9b2acc9af147ea85")
+                    .unwrap(),
+                id: Some(TokenIds::List(vec![id("1"), id("2")])),
+            }
+        );
+    }
+
+    #[test]
+    fn supported_token_types() {
+        let address = ContractAddress::new("0x76be3b62873462d2142405439777e971754e8e77").unwrap();
+
+        assert!(TokenType::Native
+            .check_supported(RequirementType::EvmBalance)
+            .is_ok());
+        assert!(TokenType::Erc1155 {
+            address,
+            id: None,
+            mode: BalanceMode::PerId
+        }
+        .check_supported(RequirementType::EvmBalance)
+        .is_ok());
+
+        let spl: TokenType = serde_json::from_str(
+            r#"{"SplToken":{"mint":"EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            spl.check_supported(RequirementType::EvmBalance)
+                .unwrap_err()
+                .to_string(),
+            "SPL tokens are not supported by evm_balance requirements"
+        );
+    }
+}
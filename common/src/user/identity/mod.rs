@@ -0,0 +1,410 @@
+use super::{
+    insert_identity, normalize_identities, HashMap, IdentityMetadata, IdentityMetadataMap, User,
+};
+use primitive_types::H160 as Address;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use verify::{Challenge, NonceStore, Proof, VerificationError};
+
+pub use parse::to_checksum_address;
+
+mod parse;
+pub mod verify;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum IdentityError {
+    #[error("Unknown identity kind {0}")]
+    UnknownKind(String),
+    #[error("EVM address {0} is missing the 0x prefix")]
+    MissingHexPrefix(String),
+    #[error("EVM address {address} has {length} hex digits instead of 40")]
+    InvalidAddressLength { address: String, length: usize },
+    #[error("EVM address {0} contains non-hex characters")]
+    InvalidHex(String),
+    #[error("EVM address {address} has an invalid EIP-55 checksum, expected {expected}")]
+    InvalidChecksum { address: String, expected: String },
+    #[error("Solana pubkey {pubkey} is not valid base58: {reason}")]
+    InvalidBase58 { pubkey: String, reason: String },
+    #[error("Solana pubkey {pubkey} decodes to {length} bytes instead of 32")]
+    InvalidPubkeyLength { pubkey: String, length: usize },
+    #[error("{kind} {value} is not a non-negative integer")]
+    InvalidNumericId { kind: String, value: String },
+    #[error("GitHub login {0} must be 1-39 alphanumeric characters or single inner hyphens")]
+    InvalidGithubLogin(String),
+    #[error("Email address {0} is malformed")]
+    InvalidEmail(String),
+    #[error("Cosmos address {address} is invalid: {reason}")]
+    InvalidCosmosAddress { address: String, reason: String },
+    #[error("Bitcoin address {address} is invalid: {reason}")]
+    InvalidBitcoinAddress { address: String, reason: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "RawIdentity", into = "RawIdentity")]
+pub enum Identity {
+    EvmAddress(Address),
+    SolPubkey(String),
+    TwitterId(u64),
+    DiscordId(u64),
+    GithubId(u64),
+    GithubLogin(String),
+    TelegramId(u64),
+    Email(String),
+    CosmosAddress(String),
+    BitcoinAddress(String),
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawIdentity {
+    kind: String,
+    value: String,
+}
+
+impl Identity {
+    pub const KINDS: [&'static str; 10] = [
+        "evm_address",
+        "sol_pubkey",
+        "twitter_id",
+        "discord_id",
+        "github_id",
+        "github_login",
+        "telegram_id",
+        "email",
+        "cosmos_address",
+        "bitcoin_address",
+    ];
+
+    pub fn from_kind_and_value(kind: &str, value: &str) -> Result<Self, IdentityError> {
+        match kind {
+            "evm_address" => parse::evm_address(value).map(Self::EvmAddress),
+            "sol_pubkey" => parse::sol_pubkey(value).map(Self::SolPubkey),
+            "twitter_id" => parse::numeric_id(kind, value).map(Self::TwitterId),
+            "discord_id" => parse::numeric_id(kind, value).map(Self::DiscordId),
+            "github_id" => parse::numeric_id(kind, value).map(Self::GithubId),
+            "github_login" => parse::github_login(value).map(Self::GithubLogin),
+            "telegram_id" => parse::numeric_id(kind, value).map(Self::TelegramId),
+            "email" => parse::email(value).map(Self::Email),
+            "cosmos_address" => parse::cosmos_address(value).map(Self::CosmosAddress),
+            "bitcoin_address" => parse::bitcoin_address(value).map(Self::BitcoinAddress),
+            _ => Err(IdentityError::UnknownKind(kind.to_string())),
+        }
+    }
+
+    pub fn id(&self) -> String {
+        match self {
+            Self::EvmAddress(_) => "evm_address",
+            Self::SolPubkey(_) => "sol_pubkey",
+            Self::TwitterId(_) => "twitter_id",
+            Self::DiscordId(_) => "discord_id",
+            Self::GithubId(_) => "github_id",
+            Self::GithubLogin(_) => "github_login",
+            Self::TelegramId(_) => "telegram_id",
+            Self::Email(_) => "email",
+            Self::CosmosAddress(_) => "cosmos_address",
+            Self::BitcoinAddress(_) => "bitcoin_address",
+        }
+        .to_string()
+    }
+
+    pub fn inner(&self) -> String {
+        match self {
+            Self::EvmAddress(address) => format!("{address:#x}"),
+            Self::TwitterId(id)
+            | Self::DiscordId(id)
+            | Self::GithubId(id)
+            | Self::TelegramId(id) => format!("{id}"),
+            Self::SolPubkey(value)
+            | Self::GithubLogin(value)
+            | Self::Email(value)
+            | Self::CosmosAddress(value)
+            | Self::BitcoinAddress(value) => value.to_string(),
+        }
+    }
+}
+
+impl TryFrom<RawIdentity> for Identity {
+    type Error = IdentityError;
+
+    fn try_from(raw: RawIdentity) -> Result<Self, Self::Error> {
+        Self::from_kind_and_value(&raw.kind, &raw.value)
+    }
+}
+
+impl From<Identity> for RawIdentity {
+    fn from(identity: Identity) -> Self {
+        Self {
+            kind: identity.id(),
+            value: identity.inner(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserBuilder {
+    pub id: u64,
+    pub identities: HashMap<String, Vec<String>>,
+    pub metadata: IdentityMetadataMap,
+}
+
+impl UserBuilder {
+    pub fn new(id: u64) -> Self {
+        Self {
+            id,
+            identities: HashMap::new(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    pub fn add_identity(mut self, identity: Identity) -> Self {
+        insert_identity(&mut self.identities, &identity.id(), &identity.inner());
+
+        self
+    }
+
+    pub fn add_identity_with_metadata(
+        mut self,
+        identity: Identity,
+        metadata: IdentityMetadata,
+    ) -> Self {
+        let entries = self.metadata.entry(identity.id()).or_default();
+        if metadata.primary {
+            entries.values_mut().for_each(|entry| entry.primary = false);
+        }
+        entries.insert(identity.inner(), metadata);
+
+        self.add_identity(identity)
+    }
+
+    // adds the identity of the challenge once the proof shows it is owned by
+    // the user
+    pub fn add_verified_identity(
+        self,
+        challenge: &Challenge,
+        proof: &Proof,
+        domain: &str,
+        max_ttl: u64,
+        now: u64,
+        nonces: &mut impl NonceStore,
+    ) -> Result<Self, VerificationError> {
+        challenge.verify(proof, domain, max_ttl, now, nonces)?;
+
+        let metadata = IdentityMetadata {
+            linked_at: Some(now),
+            primary: false,
+            verification: Some(proof.method()),
+        };
+
+        Ok(self.add_identity_with_metadata(challenge.identity.clone(), metadata))
+    }
+
+    pub fn build(self) -> User {
+        let mut user = User {
+            id: self.id,
+            identities: normalize_identities(self.identities),
+            metadata: HashMap::new(),
+        };
+
+        for (kind, entries) in self.metadata {
+            for (value, metadata) in entries {
+                user.set_identity_metadata(&kind, &value, metadata);
+            }
+        }
+
+        user
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{to_checksum_address, Identity, IdentityError};
+    use primitive_types::H160 as Address;
+    use std::str::FromStr;
+
+    #[test]
+    fn identity_test() {
+        let twitter = Identity::TwitterId(69420);
+
+        assert_eq!(twitter.id(), "twitter_id");
+        assert_eq!(twitter.inner(), "69420");
+
+        let evm_address = Identity::EvmAddress(
+            Address::from_str("0xe43878ce78934fe8007748ff481f03b8ee3b97de").unwrap(),
+        );
+
+        assert_eq!(evm_address.id(), "evm_address");
+        assert_eq!(
+            evm_address.inner(),
+            "0xe43878ce78934fe8007748ff481f03b8ee3b97de"
+        );
+    }
+
+    #[test]
+    fn identity_from_kind_and_value() {
+        let checksummed = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        let evm_address = Identity::from_kind_and_value("evm_address", checksummed).unwrap();
+
+        assert_eq!(
+            evm_address.inner(),
+            "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"
+        );
+        assert_eq!(
+            Identity::from_kind_and_value("evm_address", &checksummed.to_lowercase()),
+            Ok(evm_address.clone())
+        );
+        if let Identity::EvmAddress(address) = evm_address {
+            assert_eq!(to_checksum_address(&address), checksummed);
+        }
+
+        assert_eq!(
+            Identity::from_kind_and_value(
+                "evm_address",
+                "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD"
+            ),
+            Err(IdentityError::InvalidChecksum {
+                address: "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD".to_string(),
+                expected: checksummed.to_string(),
+            })
+        );
+        assert_eq!(
+            Identity::from_kind_and_value(
+                "evm_address",
+                "5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"
+            ),
+            Err(IdentityError::MissingHexPrefix(
+                "5aaeb6053f3e94c9b9a09f33669435e7ef1beaed".to_string()
+            ))
+        );
+        assert!(matches!(
+            Identity::from_kind_and_value("evm_address", "0x5aaeb6"),
+            Err(IdentityError::InvalidAddressLength { length: 6, .. })
+        ));
+        assert!(matches!(
+            Identity::from_kind_and_value(
+                "evm_address",
+                "0xzaaeb6053f3e94c9b9a09f33669435e7ef1beaed"
+            ),
+            Err(IdentityError::InvalidHex(_))
+        ));
+
+        let pubkey = "5MmxFuLhPMYeCW6U6CAYNPxxUXTY8sAhjWU3rBaY76pF";
+        assert_eq!(
+            Identity::from_kind_and_value("sol_pubkey", pubkey),
+            Ok(Identity::SolPubkey(pubkey.to_string()))
+        );
+        assert!(matches!(
+            Identity::from_kind_and_value("sol_pubkey", "0xdeadbeef"),
+            Err(IdentityError::InvalidBase58 { .. })
+        ));
+        assert!(matches!(
+            Identity::from_kind_and_value("sol_pubkey", "5MmxFuLhPMYeCW6U6CAY"),
+            Err(IdentityError::InvalidPubkeyLength { .. })
+        ));
+
+        assert_eq!(
+            Identity::from_kind_and_value("twitter_id", "69420"),
+            Ok(Identity::TwitterId(69420))
+        );
+        assert_eq!(
+            Identity::from_kind_and_value("twitter_id", "@guildxyz"),
+            Err(IdentityError::InvalidNumericId {
+                kind: "twitter_id".to_string(),
+                value: "@guildxyz".to_string()
+            })
+        );
+        assert_eq!(
+            Identity::from_kind_and_value("farcaster_id", "1"),
+            Err(IdentityError::UnknownKind("farcaster_id".to_string()))
+        );
+    }
+
+    #[test]
+    fn identity_serde() {
+        let identity = Identity::TwitterId(69420);
+        let json = serde_json::to_string(&identity).unwrap();
+
+        assert_eq!(json, r#"{"kind":"twitter_id","value":"69420"}"#);
+        assert_eq!(serde_json::from_str::<Identity>(&json).unwrap(), identity);
+
+        let invalid = serde_json::from_str::<Identity>(r#"{"kind":"sol_pubkey","value":"abc"}"#)
+            .unwrap_err()
+            .to_string();
+
+        assert!(invalid.starts_with("Solana pubkey abc decodes to 3 bytes instead of 32"));
+    }
+
+    #[test]
+    fn social_identities() {
+        assert_eq!(
+            Identity::from_kind_and_value("discord_id", "385866706185306112"),
+            Ok(Identity::DiscordId(385866706185306112))
+        );
+        assert!(Identity::from_kind_and_value("telegram_id", "-1").is_err());
+        assert_eq!(
+            Identity::from_kind_and_value("github_login", "Agora-XYZ"),
+            Ok(Identity::GithubLogin("agora-xyz".to_string()))
+        );
+        for login in ["", "-agora", "agora-", "ago--ra", "agora_xyz"] {
+            assert_eq!(
+                Identity::from_kind_and_value("github_login", login),
+                Err(IdentityError::InvalidGithubLogin(login.to_string()))
+            );
+        }
+        assert_eq!(
+            Identity::from_kind_and_value("email", "Shronk@Guild.XYZ"),
+            Ok(Identity::Email("Shronk@guild.xyz".to_string()))
+        );
+        for email in ["shronk", "shronk@guild", "@guild.xyz", "shr onk@guild.xyz"] {
+            assert_eq!(
+                Identity::from_kind_and_value("email", email),
+                Err(IdentityError::InvalidEmail(email.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn chain_identities() {
+        let cosmos = "cosmos1hsk6jryyqjfhp5dhc55tc9jtckygx0eph6dd02";
+
+        assert_eq!(
+            Identity::from_kind_and_value("cosmos_address", &cosmos.to_uppercase()),
+            Ok(Identity::CosmosAddress(cosmos.to_string()))
+        );
+        assert!(matches!(
+            Identity::from_kind_and_value(
+                "cosmos_address",
+                "cosmos1hsk6jryyqjfhp5dhc55tc9jtckygx0eph6dd03"
+            ),
+            Err(IdentityError::InvalidCosmosAddress { .. })
+        ));
+
+        for address in [
+            "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa",
+            "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy",
+            "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq",
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+        ] {
+            assert_eq!(
+                Identity::from_kind_and_value("bitcoin_address", address),
+                Ok(Identity::BitcoinAddress(address.to_string()))
+            );
+        }
+
+        for address in [
+            "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb",
+            "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdr",
+            cosmos,
+        ] {
+            assert!(matches!(
+                Identity::from_kind_and_value("bitcoin_address", address),
+                Err(IdentityError::InvalidBitcoinAddress { .. })
+            ));
+        }
+
+        for kind in Identity::KINDS {
+            assert!(!matches!(
+                Identity::from_kind_and_value(kind, ""),
+                Err(IdentityError::UnknownKind(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use super::{Address, IdentityError};
+use bech32::{FromBase32, Variant};
+use tiny_keccak::{Hasher, Keccak};
+
+pub(super) fn keccak256(bytes: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    let mut keccak = Keccak::v256();
+    keccak.update(bytes);
+    keccak.finalize(&mut hash);
+
+    hash
+}
+
+pub(super) fn decode_hex(digits: &str) -> Option<Vec<u8>> {
+    if digits.len() % 2 == 1 {
+        return None;
+    }
+
+    digits
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        })
+        .collect()
+}
+
+pub fn to_checksum_address(address: &Address) -> String {
+    let lower = format!("{address:x}");
+    let hash = keccak256(lower.as_bytes());
+
+    let checksummed = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = if i % 2 == 0 {
+                hash[i / 2] >> 4
+            } else {
+                hash[i / 2] & 0x0f
+            };
+
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+
+    format!("0x{checksummed}")
+}
+
+pub(super) fn evm_address(value: &str) -> Result<Address, IdentityError> {
+    let digits = value
+        .strip_prefix("0x")
+        .ok_or_else(|| IdentityError::MissingHexPrefix(value.to_string()))?;
+
+    if digits.len() != 40 {
+        return Err(IdentityError::InvalidAddressLength {
+            address: value.to_string(),
+            length: digits.len(),
+        });
+    }
+
+    let address = decode_hex(digits)
+        .map(|bytes| Address::from_slice(&bytes))
+        .ok_or_else(|| IdentityError::InvalidHex(value.to_string()))?;
+
+    let is_mixed_case = digits.chars().any(|c| c.is_ascii_lowercase())
+        && digits.chars().any(|c| c.is_ascii_uppercase());
+
+    if is_mixed_case {
+        let expected = to_checksum_address(&address);
+        if expected != value {
+            return Err(IdentityError::InvalidChecksum {
+                address: value.to_string(),
+                expected,
+            });
+        }
+    }
+
+    Ok(address)
+}
+
+pub(super) fn sol_pubkey(value: &str) -> Result<String, IdentityError> {
+    let bytes = bs58::decode(value)
+        .into_vec()
+        .map_err(|err| IdentityError::InvalidBase58 {
+            pubkey: value.to_string(),
+            reason: err.to_string(),
+        })?;
+
+    if bytes.len() != 32 {
+        return Err(IdentityError::InvalidPubkeyLength {
+            pubkey: value.to_string(),
+            length: bytes.len(),
+        });
+    }
+
+    Ok(value.to_string())
+}
+
+pub(super) fn numeric_id(kind: &str, value: &str) -> Result<u64, IdentityError> {
+    value.parse().map_err(|_| IdentityError::InvalidNumericId {
+        kind: kind.to_string(),
+        value: value.to_string(),
+    })
+}
+
+// logins are case-insensitive on GitHub, so the lowercase form is canonical
+pub(super) fn github_login(value: &str) -> Result<String, IdentityError> {
+    let is_valid = (1..=39).contains(&value.len())
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !value.starts_with('-')
+        && !value.ends_with('-')
+        && !value.contains("--");
+
+    if !is_valid {
+        return Err(IdentityError::InvalidGithubLogin(value.to_string()));
+    }
+
+    Ok(value.to_ascii_lowercase())
+}
+
+// only the domain is case-insensitive, the local part is kept as it is
+pub(super) fn email(value: &str) -> Result<String, IdentityError> {
+    let invalid = || IdentityError::InvalidEmail(value.to_string());
+
+    let (local, domain) = value.rsplit_once('@').ok_or_else(invalid)?;
+
+    let local_is_valid = !local.is_empty()
+        && local.len() <= 64
+        && !local.contains('@')
+        && local.chars().all(|c| c.is_ascii_graphic());
+    let domain_is_valid = domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+
+    if !local_is_valid || !domain_is_valid || value.len() > 254 {
+        return Err(invalid());
+    }
+
+    Ok(format!("{local}@{}", domain.to_ascii_lowercase()))
+}
+
+pub(super) fn cosmos_address(value: &str) -> Result<String, IdentityError> {
+    let invalid = |reason: String| IdentityError::InvalidCosmosAddress {
+        address: value.to_string(),
+        reason,
+    };
+
+    let (hrp, data, variant) = bech32::decode(value).map_err(|err| invalid(err.to_string()))?;
+
+    if variant != Variant::Bech32 {
+        return Err(invalid("expected bech32 encoding, got bech32m".to_string()));
+    }
+
+    let bytes = Vec::<u8>::from_base32(&data).map_err(|err| invalid(err.to_string()))?;
+
+    if bytes.len() != 20 && bytes.len() != 32 {
+        return Err(invalid(format!(
+            "payload is {} bytes instead of 20 or 32",
+            bytes.len()
+        )));
+    }
+
+    if hrp == "bc" || hrp == "tb" {
+        return Err(invalid(format!("{hrp} is a bitcoin prefix")));
+    }
+
+    Ok(value.to_ascii_lowercase())
+}
+
+pub(super) fn bitcoin_address(value: &str) -> Result<String, IdentityError> {
+    let invalid = |reason: String| IdentityError::InvalidBitcoinAddress {
+        address: value.to_string(),
+        reason,
+    };
+
+    let lower = value.to_ascii_lowercase();
+    if lower.starts_with("bc1") || lower.starts_with("tb1") {
+        let (_, data, variant) = bech32::decode(value).map_err(|err| invalid(err.to_string()))?;
+
+        let (version, program) = data
+            .split_first()
+            .ok_or_else(|| invalid("missing witness version".to_string()))?;
+        let version = version.to_u8();
+        let program = Vec::<u8>::from_base32(program).map_err(|err| invalid(err.to_string()))?;
+
+        let expected_variant = if version == 0 {
+            Variant::Bech32
+        } else {
+            Variant::Bech32m
+        };
+
+        if version > 16 {
+            return Err(invalid(format!("unknown witness version {version}")));
+        }
+        if variant != expected_variant {
+            return Err(invalid(format!(
+                "witness version {version} requires {expected_variant:?} encoding"
+            )));
+        }
+        if !(2..=40).contains(&program.len())
+            || (version == 0 && program.len() != 20 && program.len() != 32)
+        {
+            return Err(invalid(format!(
+                "witness program is {} bytes long",
+                program.len()
+            )));
+        }
+
+        return Ok(lower);
+    }
+
+    let payload = bs58::decode(value)
+        .with_check(None)
+        .into_vec()
+        .map_err(|err| invalid(err.to_string()))?;
+
+    match payload.split_first() {
+        // p2pkh and p2sh on mainnet and testnet
+        Some((0x00 | 0x05 | 0x6f | 0xc4, hash)) if hash.len() == 20 => Ok(value.to_string()),
+        Some((version, hash)) if hash.len() == 20 => {
+            Err(invalid(format!("unknown version byte {version:#04x}")))
+        }
+        _ => Err(invalid(format!(
+            "payload is {} bytes instead of 21",
+            payload.len()
+        ))),
+    }
+}
@@ -0,0 +1,538 @@
+use super::{
+    parse::{decode_hex, keccak256},
+    Address, Identity,
+};
+use crate::VerificationMethod;
+use ed25519_dalek::{Signature as Ed25519Signature, Verifier, VerifyingKey as Ed25519Key};
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+const EIP712_DOMAIN_TYPE: &str = "EIP712Domain(string name,string version)";
+const EIP712_CHALLENGE_TYPE: &str =
+    "Challenge(address wallet,string nonce,uint256 issuedAt,uint256 expiresAt)";
+const EIP712_VERSION: &str = "1";
+const MIN_NONCE_LENGTH: usize = 8;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum VerificationError {
+    #[error("Nonce {0} must be at least 8 alphanumeric characters")]
+    InvalidNonce(String),
+    #[error("Challenge is not valid before {issued_at}, current time is {now}")]
+    NotYetValid { issued_at: u64, now: u64 },
+    #[error("Challenge expired at {expires_at}, current time is {now}")]
+    Expired { expires_at: u64, now: u64 },
+    #[error("Challenge was issued for {found} instead of {expected}")]
+    DomainMismatch { expected: String, found: String },
+    #[error("Challenge is valid for {ttl} seconds, at most {max_ttl} are allowed")]
+    TtlTooLong { ttl: u64, max_ttl: u64 },
+    #[error("Nonce {0} was not issued or has already been used")]
+    UnknownNonce(String),
+    #[error("{proof} proofs cannot verify {kind} identities")]
+    UnsupportedProof { kind: String, proof: String },
+    #[error("Malformed signature: {0}")]
+    MalformedSignature(String),
+    #[error("Signature was made by {recovered} instead of {expected}")]
+    SignerMismatch { expected: String, recovered: String },
+    #[error("Signature does not match the challenge")]
+    InvalidSignature,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    pub domain: String,
+    pub identity: Identity,
+    pub nonce: String,
+    pub issued_at: u64,
+    pub expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", content = "signature", rename_all = "snake_case")]
+pub enum Proof {
+    Eip191(String),
+    Eip712(String),
+    Ed25519(String),
+}
+
+// keeps the nonces handed out by the server, each of them can be consumed
+// once before it expires
+pub trait NonceStore {
+    fn issue(&mut self, nonce: &str, expires_at: u64) -> bool;
+    fn consume(&mut self, nonce: &str, now: u64) -> bool;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryNonceStore {
+    issued: HashMap<String, u64>,
+}
+
+impl NonceStore for MemoryNonceStore {
+    fn issue(&mut self, nonce: &str, expires_at: u64) -> bool {
+        if self.issued.contains_key(nonce) {
+            return false;
+        }
+
+        self.issued.insert(nonce.to_string(), expires_at);
+
+        true
+    }
+
+    fn consume(&mut self, nonce: &str, now: u64) -> bool {
+        self.issued.retain(|_, expiry| *expiry > now);
+        self.issued.remove(nonce).is_some()
+    }
+}
+
+impl Challenge {
+    pub fn new(
+        domain: &str,
+        identity: Identity,
+        nonce: &str,
+        issued_at: u64,
+        ttl: u64,
+    ) -> Result<Self, VerificationError> {
+        if nonce.len() < MIN_NONCE_LENGTH || !nonce.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(VerificationError::InvalidNonce(nonce.to_string()));
+        }
+
+        Ok(Self {
+            domain: domain.to_string(),
+            identity,
+            nonce: nonce.to_string(),
+            issued_at,
+            expires_at: issued_at.saturating_add(ttl),
+        })
+    }
+
+    // creates a challenge and records its nonce as issued by the server
+    pub fn issue(
+        domain: &str,
+        identity: Identity,
+        nonce: &str,
+        now: u64,
+        ttl: u64,
+        nonces: &mut impl NonceStore,
+    ) -> Result<Self, VerificationError> {
+        let challenge = Self::new(domain, identity, nonce, now, ttl)?;
+
+        if !nonces.issue(&challenge.nonce, challenge.expires_at) {
+            return Err(VerificationError::InvalidNonce(challenge.nonce));
+        }
+
+        Ok(challenge)
+    }
+
+    pub fn message(&self) -> String {
+        format!(
+            "{} wants you to prove ownership of {} {}.\n\nNonce: {}\nIssued At: {}\nExpiration Time: {}",
+            self.domain,
+            self.identity.id(),
+            self.identity.inner(),
+            self.nonce,
+            self.issued_at,
+            self.expires_at
+        )
+    }
+
+    pub fn eip191_hash(&self) -> [u8; 32] {
+        let message = self.message();
+        let prefixed = format!("\x19Ethereum Signed Message:\n{}{message}", message.len());
+
+        keccak256(prefixed.as_bytes())
+    }
+
+    pub fn eip712_hash(&self) -> Option<[u8; 32]> {
+        let Identity::EvmAddress(wallet) = &self.identity else {
+            return None;
+        };
+
+        let mut domain = keccak256(EIP712_DOMAIN_TYPE.as_bytes()).to_vec();
+        domain.extend(keccak256(self.domain.as_bytes()));
+        domain.extend(keccak256(EIP712_VERSION.as_bytes()));
+
+        let mut challenge = keccak256(EIP712_CHALLENGE_TYPE.as_bytes()).to_vec();
+        challenge.extend([0u8; 12]);
+        challenge.extend(wallet.as_bytes());
+        challenge.extend(keccak256(self.nonce.as_bytes()));
+        challenge.extend(uint256(self.issued_at));
+        challenge.extend(uint256(self.expires_at));
+
+        let mut digest = vec![0x19, 0x01];
+        digest.extend(keccak256(&domain));
+        digest.extend(keccak256(&challenge));
+
+        Some(keccak256(&digest))
+    }
+
+    pub fn verify(
+        &self,
+        proof: &Proof,
+        domain: &str,
+        max_ttl: u64,
+        now: u64,
+        nonces: &mut impl NonceStore,
+    ) -> Result<(), VerificationError> {
+        if self.domain != domain {
+            return Err(VerificationError::DomainMismatch {
+                expected: domain.to_string(),
+                found: self.domain.clone(),
+            });
+        }
+
+        let ttl = self.expires_at.saturating_sub(self.issued_at);
+
+        if ttl > max_ttl {
+            return Err(VerificationError::TtlTooLong { ttl, max_ttl });
+        }
+        if now < self.issued_at {
+            return Err(VerificationError::NotYetValid {
+                issued_at: self.issued_at,
+                now,
+            });
+        }
+        if now >= self.expires_at {
+            return Err(VerificationError::Expired {
+                expires_at: self.expires_at,
+                now,
+            });
+        }
+
+        match (&self.identity, proof) {
+            (Identity::EvmAddress(address), Proof::Eip191(signature)) => {
+                verify_evm(address, &self.eip191_hash(), signature)?
+            }
+            (Identity::EvmAddress(address), Proof::Eip712(signature)) => {
+                let hash = self
+                    .eip712_hash()
+                    .ok_or(VerificationError::InvalidSignature)?;
+                verify_evm(address, &hash, signature)?
+            }
+            (Identity::SolPubkey(pubkey), Proof::Ed25519(signature)) => {
+                verify_ed25519(pubkey, self.message().as_bytes(), signature)?
+            }
+            (identity, proof) => {
+                return Err(VerificationError::UnsupportedProof {
+                    kind: identity.id(),
+                    proof: proof.name().to_string(),
+                })
+            }
+        }
+
+        if !nonces.consume(&self.nonce, now) {
+            return Err(VerificationError::UnknownNonce(self.nonce.clone()));
+        }
+
+        Ok(())
+    }
+}
+
+impl Proof {
+    pub fn method(&self) -> VerificationMethod {
+        match self {
+            Self::Eip191(_) => VerificationMethod::Eip191,
+            Self::Eip712(_) => VerificationMethod::Eip712,
+            Self::Ed25519(_) => VerificationMethod::Ed25519,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Eip191(_) => "eip191",
+            Self::Eip712(_) => "eip712",
+            Self::Ed25519(_) => "ed25519",
+        }
+    }
+}
+
+pub fn recover_address(hash: &[u8; 32], signature: &str) -> Result<Address, VerificationError> {
+    let malformed = |reason: &str| VerificationError::MalformedSignature(reason.to_string());
+
+    let bytes = signature
+        .strip_prefix("0x")
+        .and_then(decode_hex)
+        .ok_or_else(|| malformed("expected 0x prefixed hex"))?;
+
+    if bytes.len() != 65 {
+        return Err(malformed("expected 65 bytes"));
+    }
+
+    let signature =
+        Signature::from_slice(&bytes[..64]).map_err(|err| malformed(&err.to_string()))?;
+    let v = bytes[64];
+    let recovery_id = RecoveryId::from_byte(if v >= 27 { v - 27 } else { v })
+        .ok_or_else(|| malformed("invalid recovery id"))?;
+
+    let key = VerifyingKey::recover_from_prehash(hash, &signature, recovery_id)
+        .map_err(|_| VerificationError::InvalidSignature)?;
+    let point = key.to_encoded_point(false);
+
+    Ok(Address::from_slice(
+        &keccak256(&point.as_bytes()[1..])[12..],
+    ))
+}
+
+fn verify_evm(
+    expected: &Address,
+    hash: &[u8; 32],
+    signature: &str,
+) -> Result<(), VerificationError> {
+    let recovered = recover_address(hash, signature)?;
+
+    if &recovered != expected {
+        return Err(VerificationError::SignerMismatch {
+            expected: format!("{expected:#x}"),
+            recovered: format!("{recovered:#x}"),
+        });
+    }
+
+    Ok(())
+}
+
+fn verify_ed25519(pubkey: &str, message: &[u8], signature: &str) -> Result<(), VerificationError> {
+    let malformed = |reason: String| VerificationError::MalformedSignature(reason);
+
+    let key: [u8; 32] = bs58::decode(pubkey)
+        .into_vec()
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| malformed(format!("invalid ed25519 pubkey {pubkey}")))?;
+    let key = Ed25519Key::from_bytes(&key).map_err(|err| malformed(err.to_string()))?;
+
+    let signature: [u8; 64] = bs58::decode(signature)
+        .into_vec()
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| malformed("expected 64 base58 encoded bytes".to_string()))?;
+
+    key.verify(message, &Ed25519Signature::from_bytes(&signature))
+        .map_err(|_| VerificationError::InvalidSignature)
+}
+
+fn uint256(value: u64) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+
+    word
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::user::identity::UserBuilder;
+    use ed25519_dalek::{Signer, SigningKey as Ed25519SigningKey};
+    use k256::ecdsa::SigningKey;
+
+    const NOW: u64 = 1_690_000_000;
+    const DOMAIN: &str = "guild.xyz";
+    const MAX_TTL: u64 = 600;
+
+    fn evm_sign(key: &SigningKey, hash: &[u8; 32]) -> String {
+        let (signature, recovery_id) = key.sign_prehash_recoverable(hash).unwrap();
+        let mut bytes = signature.to_bytes().to_vec();
+        bytes.push(recovery_id.to_byte() + 27);
+
+        format!(
+            "0x{}",
+            bytes.iter().map(|b| format!("{b:02x}")).collect::<String>()
+        )
+    }
+
+    fn evm_identity(key: &SigningKey) -> Identity {
+        let point = key.verifying_key().to_encoded_point(false);
+
+        Identity::EvmAddress(Address::from_slice(
+            &keccak256(&point.as_bytes()[1..])[12..],
+        ))
+    }
+
+    #[test]
+    fn personal_sign_recovery() {
+        let message = "Some data";
+        let prefixed = format!("\x19Ethereum Signed Message:\n{}{message}", message.len());
+        let signature = "0xb91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c";
+
+        assert_eq!(
+            format!(
+                "{:#x}",
+                recover_address(&keccak256(prefixed.as_bytes()), signature).unwrap()
+            ),
+            "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23"
+        );
+    }
+
+    #[test]
+    fn evm_challenges() {
+        let key = SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let identity = evm_identity(&key);
+        let mut nonces = MemoryNonceStore::default();
+        let challenge = Challenge::issue(
+            DOMAIN,
+            identity.clone(),
+            "a1b2c3d4e5",
+            NOW,
+            300,
+            &mut nonces,
+        )
+        .unwrap();
+
+        let personal = Proof::Eip191(evm_sign(&key, &challenge.eip191_hash()));
+        let typed = Proof::Eip712(evm_sign(&key, &challenge.eip712_hash().unwrap()));
+
+        assert_eq!(
+            challenge.verify(&typed, DOMAIN, MAX_TTL, NOW + 301, &mut nonces),
+            Err(VerificationError::Expired {
+                expires_at: NOW + 300,
+                now: NOW + 301
+            })
+        );
+        assert_eq!(
+            challenge.verify(&typed, "evil.xyz", MAX_TTL, NOW, &mut nonces),
+            Err(VerificationError::DomainMismatch {
+                expected: "evil.xyz".to_string(),
+                found: DOMAIN.to_string()
+            })
+        );
+        assert!(matches!(
+            challenge.verify(
+                &Proof::Eip191(evm_sign(&key, &[0u8; 32])),
+                DOMAIN,
+                MAX_TTL,
+                NOW,
+                &mut nonces
+            ),
+            Err(VerificationError::SignerMismatch { .. })
+        ));
+
+        let user = UserBuilder::new(1)
+            .add_verified_identity(&challenge, &personal, DOMAIN, MAX_TTL, NOW + 1, &mut nonces)
+            .unwrap()
+            .build();
+
+        assert_eq!(user.identities("evm_address").unwrap(), &[identity.inner()]);
+        assert_eq!(
+            user.identity_metadata("evm_address", &identity.inner())
+                .unwrap()
+                .verification,
+            Some(VerificationMethod::Eip191)
+        );
+        assert_eq!(
+            challenge.verify(&typed, DOMAIN, MAX_TTL, NOW + 2, &mut nonces),
+            Err(VerificationError::UnknownNonce("a1b2c3d4e5".to_string()))
+        );
+
+        // nonces the server never handed out are rejected
+        let forged = Challenge::new(DOMAIN, identity.clone(), "f6g7h8i9j0", NOW, 300).unwrap();
+        let typed = Proof::Eip712(evm_sign(&key, &forged.eip712_hash().unwrap()));
+
+        assert_eq!(
+            forged.verify(&typed, DOMAIN, MAX_TTL, NOW, &mut nonces),
+            Err(VerificationError::UnknownNonce("f6g7h8i9j0".to_string()))
+        );
+
+        let long_lived = Challenge::issue(
+            DOMAIN,
+            identity,
+            "k1l2m3n4o5",
+            NOW,
+            10 * 365 * 86400,
+            &mut nonces,
+        )
+        .unwrap();
+        let typed = Proof::Eip712(evm_sign(&key, &long_lived.eip712_hash().unwrap()));
+
+        assert_eq!(
+            long_lived.verify(&typed, DOMAIN, MAX_TTL, NOW, &mut nonces),
+            Err(VerificationError::TtlTooLong {
+                ttl: 10 * 365 * 86400,
+                max_ttl: MAX_TTL
+            })
+        );
+        assert_eq!(
+            long_lived.verify(&typed, DOMAIN, u64::MAX, NOW, &mut nonces),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn ed25519_challenges() {
+        let key = Ed25519SigningKey::from_bytes(&[9u8; 32]);
+        let pubkey = bs58::encode(key.verifying_key().as_bytes()).into_string();
+        let mut nonces = MemoryNonceStore::default();
+        let challenge = Challenge::issue(
+            DOMAIN,
+            Identity::SolPubkey(pubkey.clone()),
+            "a1b2c3d4e5",
+            NOW,
+            300,
+            &mut nonces,
+        )
+        .unwrap();
+        let signature = key.sign(challenge.message().as_bytes());
+        let proof = Proof::Ed25519(bs58::encode(signature.to_bytes()).into_string());
+
+        assert_eq!(
+            challenge.verify(&proof, DOMAIN, MAX_TTL, NOW - 1, &mut nonces),
+            Err(VerificationError::NotYetValid {
+                issued_at: NOW,
+                now: NOW - 1
+            })
+        );
+        assert_eq!(
+            challenge.verify(
+                &Proof::Eip191("0x".to_string()),
+                DOMAIN,
+                MAX_TTL,
+                NOW,
+                &mut nonces
+            ),
+            Err(VerificationError::UnsupportedProof {
+                kind: "sol_pubkey".to_string(),
+                proof: "eip191".to_string()
+            })
+        );
+
+        let forged = key.sign(b"something else");
+        let forged = Proof::Ed25519(bs58::encode(forged.to_bytes()).into_string());
+
+        assert_eq!(
+            challenge.verify(&forged, DOMAIN, MAX_TTL, NOW, &mut nonces),
+            Err(VerificationError::InvalidSignature)
+        );
+        assert_eq!(
+            challenge.verify(&proof, DOMAIN, MAX_TTL, NOW, &mut nonces),
+            Ok(())
+        );
+        assert_eq!(
+            challenge.verify(&proof, DOMAIN, MAX_TTL, NOW, &mut nonces),
+            Err(VerificationError::UnknownNonce("a1b2c3d4e5".to_string()))
+        );
+
+        let pending = Challenge::issue(
+            DOMAIN,
+            Identity::SolPubkey(pubkey.clone()),
+            "p1q2r3s4t5",
+            NOW,
+            300,
+            &mut nonces,
+        );
+
+        assert!(pending.is_ok());
+        assert_eq!(
+            Challenge::issue(
+                DOMAIN,
+                Identity::SolPubkey(pubkey),
+                "p1q2r3s4t5",
+                NOW,
+                300,
+                &mut nonces
+            ),
+            Err(VerificationError::InvalidNonce("p1q2r3s4t5".to_string()))
+        );
+        assert_eq!(
+            Challenge::new(DOMAIN, Identity::TwitterId(1), "short", NOW, 300),
+            Err(VerificationError::InvalidNonce("short".to_string()))
+        );
+    }
+}
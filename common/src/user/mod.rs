@@ -1,32 +1,410 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 
 #[cfg(feature = "identity")]
 pub mod identity;
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+type IdentityMetadataMap = HashMap<String, HashMap<String, IdentityMetadata>>;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct User {
     pub id: u64,
+    #[serde(deserialize_with = "deserialize_identities")]
     identities: HashMap<String, Vec<String>>,
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        deserialize_with = "deserialize_metadata"
+    )]
+    metadata: IdentityMetadataMap,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationMethod {
+    Eip191,
+    Eip712,
+    Ed25519,
+    Oauth,
+    Manual,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct IdentityMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub linked_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub primary: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<VerificationMethod>,
 }
 
 impl User {
+    pub fn new(id: u64) -> Self {
+        Self {
+            id,
+            identities: HashMap::new(),
+            metadata: HashMap::new(),
+        }
+    }
+
     pub fn identities(&self, id_type: &str) -> Option<&Vec<String>> {
         self.identities.get(id_type)
     }
+
+    pub fn identity_kinds(&self) -> Vec<&str> {
+        let mut kinds = self
+            .identities
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        kinds.sort_unstable();
+
+        kinds
+    }
+
+    pub fn insert_identity(&mut self, kind: &str, value: &str) -> bool {
+        insert_identity(&mut self.identities, kind, value)
+    }
+
+    pub fn remove_identity(&mut self, kind: &str, value: &str) -> bool {
+        let value = normalize_identity(kind, value);
+
+        let Some(values) = self.identities.get_mut(kind) else {
+            return false;
+        };
+        let len = values.len();
+        values.retain(|existing| existing != &value);
+        let removed = values.len() != len;
+
+        if values.is_empty() {
+            self.identities.remove(kind);
+        }
+
+        if let Some(metadata) = self.metadata.get_mut(kind) {
+            metadata.remove(&value);
+            if metadata.is_empty() {
+                self.metadata.remove(kind);
+            }
+        }
+
+        removed
+    }
+
+    // identities and metadata already present in `self` take precedence
+    pub fn merge(&mut self, other: &User) {
+        for (kind, values) in &other.identities {
+            for value in values {
+                self.insert_identity(kind, value);
+
+                let Some(metadata) = other.identity_metadata(kind, value) else {
+                    continue;
+                };
+                if self.identity_metadata(kind, value).is_none() {
+                    let primary = metadata.primary && self.primary_identity(kind).is_none();
+                    let metadata = IdentityMetadata {
+                        primary,
+                        ..metadata.clone()
+                    };
+                    self.set_identity_metadata(kind, value, metadata);
+                }
+            }
+        }
+    }
+
+    pub fn identity_metadata(&self, kind: &str, value: &str) -> Option<&IdentityMetadata> {
+        self.metadata
+            .get(kind)
+            .and_then(|metadata| metadata.get(&normalize_identity(kind, value)))
+    }
+
+    pub fn set_identity_metadata(
+        &mut self,
+        kind: &str,
+        value: &str,
+        metadata: IdentityMetadata,
+    ) -> bool {
+        let value = normalize_identity(kind, value);
+
+        if !self
+            .identities
+            .get(kind)
+            .is_some_and(|values| values.contains(&value))
+        {
+            return false;
+        }
+
+        let entries = self.metadata.entry(kind.to_string()).or_default();
+        if metadata.primary {
+            entries.values_mut().for_each(|entry| entry.primary = false);
+        }
+        entries.insert(value, metadata);
+
+        true
+    }
+
+    pub fn set_primary_identity(&mut self, kind: &str, value: &str) -> bool {
+        let metadata = IdentityMetadata {
+            primary: true,
+            ..self
+                .identity_metadata(kind, value)
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        self.set_identity_metadata(kind, value, metadata)
+    }
+
+    pub fn primary_identity(&self, kind: &str) -> Option<&str> {
+        let values = self.identities.get(kind)?;
+
+        values
+            .iter()
+            .find(|value| {
+                self.identity_metadata(kind, value)
+                    .is_some_and(|metadata| metadata.primary)
+            })
+            .map(String::as_str)
+    }
+
+    // kinds without an explicitly marked primary fall back to the first linked identity
+    pub fn with_primary_identities(&self) -> User {
+        let identities = self
+            .identities
+            .iter()
+            .filter_map(|(kind, values)| {
+                self.primary_identity(kind)
+                    .or(values.first().map(String::as_str))
+                    .map(|value| (kind.clone(), vec![value.to_string()]))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let metadata = self
+            .metadata
+            .iter()
+            .filter_map(|(kind, entries)| {
+                let primary = identities.get(kind)?;
+                let entries = entries
+                    .iter()
+                    .filter(|(value, _)| primary.contains(value))
+                    .map(|(value, metadata)| (value.clone(), metadata.clone()))
+                    .collect::<HashMap<_, _>>();
+                (!entries.is_empty()).then(|| (kind.clone(), entries))
+            })
+            .collect();
+
+        User {
+            id: self.id,
+            identities,
+            metadata,
+        }
+    }
 }
 
-#[cfg(all(test, feature = "identity"))]
+pub fn normalize_identity(kind: &str, value: &str) -> String {
+    let value = value.trim();
+
+    match kind {
+        "evm_address" | "github_login" | "cosmos_address" => value.to_ascii_lowercase(),
+        "bitcoin_address" => {
+            let lower = value.to_ascii_lowercase();
+            // only bech32 addresses are case-insensitive, base58 ones are not
+            if lower.starts_with("bc1") || lower.starts_with("tb1") {
+                lower
+            } else {
+                value.to_string()
+            }
+        }
+        "email" => match value.rsplit_once('@') {
+            Some((local, domain)) => format!("{local}@{}", domain.to_ascii_lowercase()),
+            None => value.to_string(),
+        },
+        _ => value.to_string(),
+    }
+}
+
+fn insert_identity(identities: &mut HashMap<String, Vec<String>>, kind: &str, value: &str) -> bool {
+    let value = normalize_identity(kind, value);
+    let values = identities.entry(kind.to_string()).or_default();
+
+    if values.contains(&value) {
+        return false;
+    }
+
+    values.push(value);
+
+    true
+}
+
+fn normalize_identities(identities: HashMap<String, Vec<String>>) -> HashMap<String, Vec<String>> {
+    let mut normalized = HashMap::new();
+
+    for (kind, values) in identities {
+        for value in values {
+            insert_identity(&mut normalized, &kind, &value);
+        }
+    }
+
+    normalized
+}
+
+fn normalize_metadata(metadata: IdentityMetadataMap) -> IdentityMetadataMap {
+    metadata
+        .into_iter()
+        .map(|(kind, entries)| {
+            let entries = entries
+                .into_iter()
+                .map(|(value, metadata)| (normalize_identity(&kind, &value), metadata))
+                .collect();
+            (kind, entries)
+        })
+        .collect()
+}
+
+fn deserialize_metadata<'de, D>(deserializer: D) -> Result<IdentityMetadataMap, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    HashMap::deserialize(deserializer).map(normalize_metadata)
+}
+
+fn deserialize_identities<'de, D>(deserializer: D) -> Result<HashMap<String, Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    HashMap::deserialize(deserializer).map(normalize_identities)
+}
+
+#[cfg(test)]
 mod test {
-    use super::identity::{Identity, UserBuilder};
+    use super::{IdentityMetadata, User, VerificationMethod};
 
+    #[cfg(feature = "identity")]
     #[test]
     fn add_identity_test() {
+        use super::identity::{Identity, UserBuilder};
+
         let user = UserBuilder::new(69)
             .add_identity(Identity::TwitterId(420))
             .add_identity(Identity::TwitterId(23))
+            .add_identity(Identity::TwitterId(420))
             .build();
 
         assert_eq!(user.identities("twitter_id").unwrap(), &["420", "23"]);
     }
+
+    #[test]
+    fn normalized_identities() {
+        let mut user: User = serde_json::from_str(
+            r#"{
+                "id": 0,
+                "identities": {
+                    "evm_address": [
+                        "0xE43878Ce78934fe8007748FF481f03B8Ee3b97DE",
+                        "0xe43878ce78934fe8007748ff481f03b8ee3b97de",
+                        "0x14DDFE8EA7FFc338015627D160ccAf99e8F16Dd3"
+                    ],
+                    "email": ["Shronk@Guild.XYZ", "Shronk@guild.xyz"]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            user.identities("evm_address").unwrap(),
+            &[
+                "0xe43878ce78934fe8007748ff481f03b8ee3b97de",
+                "0x14ddfe8ea7ffc338015627d160ccaf99e8f16dd3"
+            ]
+        );
+        assert_eq!(user.identities("email").unwrap(), &["Shronk@guild.xyz"]);
+        assert_eq!(user.identity_kinds(), ["email", "evm_address"]);
+
+        assert!(!user.insert_identity("evm_address", "0x14ddfe8ea7ffc338015627d160ccaf99e8f16dd3"));
+        assert!(user.remove_identity("email", "Shronk@GUILD.xyz"));
+        assert!(!user.remove_identity("email", "Shronk@guild.xyz"));
+        assert_eq!(user.identity_kinds(), ["evm_address"]);
+
+        let mut other = User::new(1);
+        other.insert_identity("evm_address", "0xE43878Ce78934fe8007748FF481f03B8Ee3b97DE");
+        other.insert_identity("discord_id", "385866706185306112");
+        user.merge(&other);
+
+        assert_eq!(user.identities("evm_address").unwrap().len(), 2);
+        assert_eq!(user.identity_kinds(), ["discord_id", "evm_address"]);
+        assert_eq!(
+            serde_json::from_str::<User>(&serde_json::to_string(&user).unwrap()).unwrap(),
+            user
+        );
+    }
+
+    #[test]
+    fn primary_identities() {
+        let mut user: User = serde_json::from_str(
+            r#"{
+                "id": 0,
+                "identities": {
+                    "evm_address": [
+                        "0xE43878Ce78934fe8007748FF481f03B8Ee3b97DE",
+                        "0x14DDFE8EA7FFc338015627D160ccAf99e8F16Dd3"
+                    ],
+                    "discord_id": ["385866706185306112"]
+                },
+                "metadata": {
+                    "evm_address": {
+                        "0x14DDFE8EA7FFc338015627D160ccAf99e8F16Dd3": {
+                            "linked_at": 1690000000,
+                            "primary": true,
+                            "verification": "eip191"
+                        }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let primary = "0x14ddfe8ea7ffc338015627d160ccaf99e8f16dd3";
+
+        assert_eq!(user.primary_identity("evm_address"), Some(primary));
+        assert_eq!(user.primary_identity("discord_id"), None);
+        assert_eq!(
+            user.identity_metadata("evm_address", primary)
+                .unwrap()
+                .verification,
+            Some(VerificationMethod::Eip191)
+        );
+
+        let primaries = user.with_primary_identities();
+
+        assert_eq!(primaries.identities("evm_address").unwrap(), &[primary]);
+        assert_eq!(
+            primaries.identities("discord_id").unwrap(),
+            &["385866706185306112"]
+        );
+
+        assert!(
+            user.set_primary_identity("evm_address", "0xE43878Ce78934fe8007748FF481f03B8Ee3b97DE")
+        );
+        assert_eq!(
+            user.primary_identity("evm_address"),
+            Some("0xe43878ce78934fe8007748ff481f03b8ee3b97de")
+        );
+        assert_eq!(
+            user.identity_metadata("evm_address", primary),
+            Some(&IdentityMetadata {
+                linked_at: Some(1690000000),
+                primary: false,
+                verification: Some(VerificationMethod::Eip191),
+            })
+        );
+        assert!(
+            !user.set_primary_identity("evm_address", "0x283d678711daa088640c86a1ad3f12c00ec1252e")
+        );
+
+        assert!(user.remove_identity("evm_address", primary));
+        assert_eq!(user.identity_metadata("evm_address", primary), None);
+    }
 }
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::cmp::PartialEq;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct AllowList<T> {
     pub deny_list: bool,
     pub list: Vec<T>,
@@ -4,16 +4,21 @@
 #![deny(unused_crate_dependencies)]
 
 pub use allowlist::AllowList;
-use guild_common::User;
-use guild_requirement::{RedisCache, Requirement};
+use guild_common::{normalize_identity, User};
+use guild_requirement::{RedisCache, Requirement, RequirementError};
 use requiem::{LogicTree, ParseError};
+use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, str::FromStr};
 use thiserror::Error;
 
 mod allowlist;
+#[cfg(feature = "schema")]
+pub mod schema;
 
 type AccessMatrix = Vec<Vec<bool>>;
 
+#[derive(Deserialize, Serialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Role {
     pub id: String,
     pub filter: Option<AllowList<String>>,
@@ -27,8 +32,8 @@ pub enum RoleError {
     InvalidRole,
     #[error(transparent)]
     Requiem(#[from] ParseError),
-    #[error("{0}")]
-    Requirement(String),
+    #[error(transparent)]
+    Requirement(#[from] RequirementError),
 }
 
 impl Role {
@@ -55,16 +60,21 @@ impl Role {
             .map(|req| req.check(redis_cache, client, users))
             .collect();
 
-        let acc_res: Result<AccessMatrix, _> = acc.into_iter().collect();
-
-        let Ok(acc_per_req) = acc_res else {
-            return Err(RoleError::Requirement(acc_res.unwrap_err().to_string()))
-        };
+        let acc_per_req: AccessMatrix = acc.into_iter().collect::<Result<_, _>>()?;
 
         let rotated: AccessMatrix = rotate_matrix(&acc_per_req, users.len());
         let res = evaluate_access_matrix(&rotated, &self.logic)?;
 
         if let Some(filter) = self.filter.as_ref() {
+            let filter = AllowList {
+                deny_list: filter.deny_list,
+                list: filter
+                    .list
+                    .iter()
+                    .map(|address| normalize_identity("evm_address", address))
+                    .collect(),
+            };
+
             let list = users
                 .iter()
                 .map(|user| {
@@ -124,7 +134,10 @@ mod test {
     use super::{
         evaluate_access_matrix, rotate_matrix, AllowList, RedisCache, Requirement, Role, User,
     };
-    use guild_common::{Chain, Relation, RequirementType, TokenType};
+    use guild_common::{
+        Aggregation, Chain, ContractAddress, IdentityScope, Relation, Scalar, TokenType,
+    };
+    use guild_requirement::{RequirementKind, TypedRequirement};
 
     const USERS: &str = r#"[
     {
@@ -197,20 +210,25 @@ mod test {
 
         let users: Vec<User> = serde_json::from_str(USERS).unwrap();
 
-        let token_type = TokenType::NonFungible {
-            address: "0x57f1887a8BF19b14fC0dF6Fd9B2acc9Af147eA85".to_string(),
+        let token_type = TokenType::Erc721 {
+            address: ContractAddress::new("0x57f1887a8BF19b14fC0dF6Fd9B2acc9Af147eA85").unwrap(),
             id: None,
         };
 
-        let relation = Relation::GreaterThan(0.0);
+        let relation = Relation::GreaterThan(Scalar::from(0));
 
-        let req = Requirement {
+        let req: Requirement = TypedRequirement {
             id: "69".to_string(),
-            typ: RequirementType::EvmBalance.to_string(),
-            config_key: Chain::Ethereum.to_string(),
-            metadata: serde_json::to_string(&token_type).unwrap(),
+            kind: RequirementKind::EvmBalance {
+                chain: Chain::Ethereum,
+                token: token_type,
+                block: None,
+            },
             relation,
-        };
+            aggregation: Aggregation::default(),
+            identity_scope: IdentityScope::default(),
+        }
+        .into();
 
         let role = Role {
             id: "420".to_string(),
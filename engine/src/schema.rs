@@ -0,0 +1,96 @@
+use crate::{AllowList, Role};
+use guild_common::{
+    Chain, ChainRegistry, EvmBalanceMetadata, EvmCallMetadata, EvmLogsMetadata, Relation,
+    RequirementType, Scalar, TokenType, User,
+};
+use guild_requirement::{Requirement, TypedRequirement};
+use schemars::{schema::RootSchema, schema_for};
+use std::collections::BTreeMap;
+
+pub fn metadata_schema(requirement_type: &RequirementType) -> RootSchema {
+    match requirement_type {
+        RequirementType::EvmBalance => schema_for!(EvmBalanceMetadata),
+        RequirementType::EvmCall => schema_for!(EvmCallMetadata),
+        RequirementType::EvmLogs => schema_for!(EvmLogsMetadata),
+        RequirementType::SolBalance => {
+            let mut schema = schema_for!(String);
+            schema.schema.string().max_length = Some(0);
+            schema
+        }
+    }
+}
+
+pub fn schemas() -> BTreeMap<String, RootSchema> {
+    let mut schemas: BTreeMap<_, _> = [
+        ("allow_list", schema_for!(AllowList<String>)),
+        ("chain", schema_for!(Chain)),
+        ("chain_registry", schema_for!(ChainRegistry)),
+        ("relation", schema_for!(Relation<Scalar>)),
+        ("requirement", schema_for!(Requirement)),
+        ("requirement_type", schema_for!(RequirementType)),
+        ("role", schema_for!(Role)),
+        ("token_type", schema_for!(TokenType)),
+        ("typed_requirement", schema_for!(TypedRequirement)),
+        ("user", schema_for!(User)),
+    ]
+    .into_iter()
+    .map(|(name, schema)| (name.to_string(), schema))
+    .collect();
+
+    for requirement_type in [
+        RequirementType::EvmBalance,
+        RequirementType::EvmCall,
+        RequirementType::EvmLogs,
+        RequirementType::SolBalance,
+    ] {
+        schemas.insert(
+            format!("{requirement_type}_metadata"),
+            metadata_schema(&requirement_type),
+        );
+    }
+
+    schemas
+}
+
+#[cfg(test)]
+mod test {
+    use super::{metadata_schema, schemas};
+    use guild_common::RequirementType;
+    use serde_json::json;
+
+    #[test]
+    fn schema_export() {
+        let schemas = schemas();
+
+        assert!(schemas.contains_key("role"));
+        assert!(schemas.contains_key("evm_balance_metadata"));
+        assert!(schemas.contains_key("evm_call_metadata"));
+        assert!(schemas.contains_key("evm_logs_metadata"));
+        assert!(schemas.contains_key("sol_balance_metadata"));
+
+        let relation = serde_json::to_value(&schemas["relation"]).unwrap();
+        let range = &relation["definitions"]["Range_of_Decimal"];
+
+        assert_eq!(range["required"], json!(["end", "start"]));
+
+        let metadata = serde_json::to_value(metadata_schema(&RequirementType::EvmBalance)).unwrap();
+        let variants = metadata["oneOf"].as_array().unwrap();
+
+        let erc20 = variants
+            .iter()
+            .find(|v| v["required"] == json!(["Erc20"]))
+            .unwrap();
+
+        assert_eq!(
+            erc20["properties"]["block"],
+            json!({ "$ref": "#/definitions/BlockSpec" })
+        );
+        assert!(variants.iter().any(|v| v["enum"] == json!(["Native"])));
+        assert!(variants.iter().any(|v| v["required"] == json!(["Native"])
+            && v["properties"]["block"] == json!({ "$ref": "#/definitions/BlockSpec" })));
+        assert!(metadata["definitions"]
+            .as_object()
+            .unwrap()
+            .contains_key("BlockSpec"));
+    }
+}
@@ -0,0 +1,734 @@
+use primitive_types::{H160 as Address, U256};
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+use tiny_keccak::{Hasher, Keccak};
+
+const WORD: usize = 32;
+// the most words a fixed size array may take up
+const MAX_FIXED_ARRAY_WORDS: usize = 1024;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AbiError {
+    #[error("Invalid ABI type {0}")]
+    InvalidType(String),
+    #[error("Invalid function signature {0}")]
+    InvalidSignature(String),
+    #[error("Invalid hex string {0}")]
+    InvalidHex(String),
+    #[error("Invalid address {0}")]
+    InvalidAddress(String),
+    #[error("ABI data too short, needed {needed} bytes but got {len}")]
+    TooShort { needed: usize, len: usize },
+    #[error("ABI offset or length {0} is out of bounds")]
+    OutOfBounds(U256),
+    #[error("Invalid boolean value {0}")]
+    InvalidBool(U256),
+    #[error("String is not valid utf-8")]
+    InvalidUtf8,
+    #[error("ABI data decodes to more than its {0} bytes")]
+    TooLarge(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamType {
+    Address,
+    Uint(usize),
+    Int(usize),
+    Bool,
+    FixedBytes(usize),
+    Bytes,
+    String,
+    Array(Box<ParamType>),
+    FixedArray(Box<ParamType>, usize),
+    Tuple(Vec<ParamType>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Address(Address),
+    Uint(U256),
+    // two's complement representation
+    Int(U256),
+    Bool(bool),
+    FixedBytes(Vec<u8>),
+    Bytes(Vec<u8>),
+    String(String),
+    Array(Vec<Token>),
+    FixedArray(Vec<Token>),
+    Tuple(Vec<Token>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub inputs: Vec<ParamType>,
+}
+
+pub fn keccak256(bytes: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    let mut keccak = Keccak::v256();
+    keccak.update(bytes);
+    keccak.finalize(&mut hash);
+
+    hash
+}
+
+pub fn selector(signature: &str) -> [u8; 4] {
+    let hash = keccak256(signature.as_bytes());
+
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+pub fn encode_call(signature: &str, tokens: &[Token]) -> Vec<u8> {
+    let mut call_data = selector(signature).to_vec();
+    call_data.extend(encode(tokens));
+
+    call_data
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    let digits = bytes
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+
+    format!("0x{digits}")
+}
+
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, AbiError> {
+    let digits = hex.strip_prefix("0x").unwrap_or(hex);
+    let invalid = || AbiError::InvalidHex(hex.to_string());
+
+    if digits.len() % 2 == 1 {
+        return Err(invalid());
+    }
+
+    digits
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+pub fn parse_address(address: &str) -> Result<Address, AbiError> {
+    match from_hex(address) {
+        Ok(bytes) if bytes.len() == 20 && address.starts_with("0x") => {
+            Ok(Address::from_slice(&bytes))
+        }
+        _ => Err(AbiError::InvalidAddress(address.to_string())),
+    }
+}
+
+impl ParamType {
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            Self::Bytes | Self::String | Self::Array(_) => true,
+            Self::FixedArray(inner, _) => inner.is_dynamic(),
+            Self::Tuple(types) => types.iter().any(Self::is_dynamic),
+            _ => false,
+        }
+    }
+
+    fn head_size(&self) -> usize {
+        match self {
+            Self::FixedArray(inner, len) if !self.is_dynamic() => inner.head_size() * len,
+            Self::Tuple(types) if !self.is_dynamic() => types.iter().map(Self::head_size).sum(),
+            _ => WORD,
+        }
+    }
+}
+
+impl fmt::Display for ParamType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address => write!(f, "address"),
+            Self::Uint(bits) => write!(f, "uint{bits}"),
+            Self::Int(bits) => write!(f, "int{bits}"),
+            Self::Bool => write!(f, "bool"),
+            Self::FixedBytes(len) => write!(f, "bytes{len}"),
+            Self::Bytes => write!(f, "bytes"),
+            Self::String => write!(f, "string"),
+            Self::Array(inner) => write!(f, "{inner}[]"),
+            Self::FixedArray(inner, len) => write!(f, "{inner}[{len}]"),
+            Self::Tuple(types) => write!(f, "({})", join_types(types)),
+        }
+    }
+}
+
+impl FromStr for ParamType {
+    type Err = AbiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AbiError::InvalidType(s.to_string());
+        let s = s.trim();
+
+        if let Some(rest) = s.strip_suffix(']') {
+            let open = rest.rfind('[').ok_or_else(invalid)?;
+            let inner = Box::new(rest[..open].parse()?);
+            let len = &rest[open + 1..];
+
+            return if len.is_empty() {
+                Ok(Self::Array(inner))
+            } else {
+                let len = len.parse::<usize>().map_err(|_| invalid())?;
+                let words = inner.head_size() / WORD * len;
+
+                if len == 0 || words > MAX_FIXED_ARRAY_WORDS {
+                    return Err(invalid());
+                }
+
+                Ok(Self::FixedArray(inner, len))
+            };
+        }
+
+        if let Some(inner) = s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+            // every type takes up at least one word
+            return split_params(inner)
+                .filter(|types| !types.is_empty())
+                .ok_or_else(invalid)?
+                .into_iter()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map(Self::Tuple);
+        }
+
+        let bits = |digits: &str| match digits {
+            "" => Some(256),
+            _ => digits
+                .parse::<usize>()
+                .ok()
+                .filter(|bits| (1..=32).any(|bytes| bytes * 8 == *bits)),
+        };
+
+        match s {
+            "address" => Ok(Self::Address),
+            "bool" => Ok(Self::Bool),
+            "bytes" => Ok(Self::Bytes),
+            "string" => Ok(Self::String),
+            _ => {
+                if let Some(digits) = s.strip_prefix("uint") {
+                    bits(digits).map(Self::Uint).ok_or_else(invalid)
+                } else if let Some(digits) = s.strip_prefix("int") {
+                    bits(digits).map(Self::Int).ok_or_else(invalid)
+                } else if let Some(digits) = s.strip_prefix("bytes") {
+                    digits
+                        .parse()
+                        .ok()
+                        .filter(|len| (1..=32).contains(len))
+                        .map(Self::FixedBytes)
+                        .ok_or_else(invalid)
+                } else {
+                    Err(invalid())
+                }
+            }
+        }
+    }
+}
+
+impl Token {
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            Self::Bytes(_) | Self::String(_) | Self::Array(_) => true,
+            Self::FixedArray(tokens) | Self::Tuple(tokens) => tokens.iter().any(Self::is_dynamic),
+            _ => false,
+        }
+    }
+
+    pub fn into_address(self) -> Option<Address> {
+        match self {
+            Self::Address(address) => Some(address),
+            _ => None,
+        }
+    }
+
+    pub fn into_uint(self) -> Option<U256> {
+        match self {
+            Self::Uint(value) | Self::Int(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn into_bool(self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn into_bytes(self) -> Option<Vec<u8>> {
+        match self {
+            Self::Bytes(bytes) | Self::FixedBytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn into_array(self) -> Option<Vec<Token>> {
+        match self {
+            Self::Array(tokens) | Self::FixedArray(tokens) => Some(tokens),
+            _ => None,
+        }
+    }
+
+    pub fn into_tuple(self) -> Option<Vec<Token>> {
+        match self {
+            Self::Tuple(tokens) => Some(tokens),
+            _ => None,
+        }
+    }
+
+    fn head_size(&self) -> usize {
+        match self {
+            Self::FixedArray(tokens) | Self::Tuple(tokens) if !self.is_dynamic() => {
+                tokens.iter().map(Self::head_size).sum()
+            }
+            _ => WORD,
+        }
+    }
+}
+
+impl Function {
+    pub fn parse(signature: &str) -> Result<Self, AbiError> {
+        let invalid = || AbiError::InvalidSignature(signature.to_string());
+
+        let (name, params) = signature.trim().split_once('(').ok_or_else(invalid)?;
+        let params = params.strip_suffix(')').ok_or_else(invalid)?;
+
+        let is_identifier = !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+        if !is_identifier {
+            return Err(invalid());
+        }
+
+        let inputs = split_params(params)
+            .ok_or_else(invalid)?
+            .into_iter()
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            name: name.to_string(),
+            inputs,
+        })
+    }
+
+    pub fn signature(&self) -> String {
+        format!("{}({})", self.name, join_types(&self.inputs))
+    }
+
+    pub fn selector(&self) -> [u8; 4] {
+        selector(&self.signature())
+    }
+
+    pub fn encode_input(&self, tokens: &[Token]) -> Vec<u8> {
+        encode_call(&self.signature(), tokens)
+    }
+}
+
+fn join_types(types: &[ParamType]) -> String {
+    types
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// splits on top-level commas, leaving nested tuples intact
+fn split_params(params: &str) -> Option<Vec<&str>> {
+    if params.trim().is_empty() {
+        return Some(vec![]);
+    }
+
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut parts = vec![];
+
+    for (i, c) in params.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.checked_sub(1)?,
+            ',' if depth == 0 => {
+                parts.push(&params[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if depth != 0 {
+        return None;
+    }
+
+    parts.push(&params[start..]);
+
+    Some(parts)
+}
+
+fn word(value: U256) -> [u8; WORD] {
+    let mut word = [0u8; WORD];
+    value.to_big_endian(&mut word);
+
+    word
+}
+
+fn padded(bytes: &[u8]) -> Vec<u8> {
+    let mut padded = bytes.to_vec();
+    padded.resize(bytes.len().div_ceil(WORD) * WORD, 0);
+
+    padded
+}
+
+pub fn encode(tokens: &[Token]) -> Vec<u8> {
+    let head_len: usize = tokens.iter().map(Token::head_size).sum();
+    let mut head = Vec::with_capacity(head_len);
+    let mut tail = vec![];
+
+    for token in tokens {
+        if token.is_dynamic() {
+            head.extend(word(U256::from(head_len + tail.len())));
+            tail.extend(encode_token(token));
+        } else {
+            head.extend(encode_token(token));
+        }
+    }
+
+    head.extend(tail);
+
+    head
+}
+
+fn encode_token(token: &Token) -> Vec<u8> {
+    match token {
+        Token::Address(address) => {
+            let mut encoded = vec![0u8; WORD - 20];
+            encoded.extend(address.as_bytes());
+            encoded
+        }
+        Token::Uint(value) | Token::Int(value) => word(*value).to_vec(),
+        Token::Bool(value) => word(U256::from(*value as u8)).to_vec(),
+        Token::FixedBytes(bytes) => padded(bytes),
+        Token::Bytes(bytes) => {
+            let mut encoded = word(U256::from(bytes.len())).to_vec();
+            encoded.extend(padded(bytes));
+            encoded
+        }
+        Token::String(string) => encode_token(&Token::Bytes(string.as_bytes().to_vec())),
+        Token::Array(tokens) => {
+            let mut encoded = word(U256::from(tokens.len())).to_vec();
+            encoded.extend(encode(tokens));
+            encoded
+        }
+        Token::FixedArray(tokens) | Token::Tuple(tokens) => encode(tokens),
+    }
+}
+
+pub fn decode(types: &[ParamType], data: &[u8]) -> Result<Vec<Token>, AbiError> {
+    let mut decoder = Decoder {
+        data,
+        words: data.len().div_ceil(WORD),
+    };
+
+    decoder.decode_params(types.iter(), 0)
+}
+
+// a valid encoding reads every word of the data at most once, so offsets
+// pointing at the same data can't multiply the size of the decoded tokens
+struct Decoder<'a> {
+    data: &'a [u8],
+    words: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn charge(&mut self, words: usize) -> Result<(), AbiError> {
+        self.words = self
+            .words
+            .checked_sub(words)
+            .ok_or(AbiError::TooLarge(self.data.len()))?;
+
+        Ok(())
+    }
+
+    fn read_word(&mut self, at: usize) -> Result<&'a [u8], AbiError> {
+        let data = self.data;
+        let word = data.get(at..at + WORD).ok_or(AbiError::TooShort {
+            needed: at + WORD,
+            len: data.len(),
+        })?;
+        self.charge(1)?;
+
+        Ok(word)
+    }
+
+    fn read_uint(&mut self, at: usize) -> Result<U256, AbiError> {
+        self.read_word(at).map(U256::from_big_endian)
+    }
+
+    fn read_usize(&mut self, at: usize) -> Result<usize, AbiError> {
+        let value = self.read_uint(at)?;
+
+        if value > U256::from(self.data.len()) {
+            return Err(AbiError::OutOfBounds(value));
+        }
+
+        Ok(value.as_usize())
+    }
+
+    fn decode_params<'t>(
+        &mut self,
+        types: impl Iterator<Item = &'t ParamType>,
+        base: usize,
+    ) -> Result<Vec<Token>, AbiError> {
+        let mut offset = base;
+
+        types
+            .map(|typ| {
+                let token = if typ.is_dynamic() {
+                    let pointer = self.read_usize(offset)?;
+                    self.decode_param(typ, base + pointer)?
+                } else {
+                    self.decode_param(typ, offset)?
+                };
+                offset += typ.head_size();
+
+                Ok(token)
+            })
+            .collect()
+    }
+
+    fn decode_param(&mut self, typ: &ParamType, at: usize) -> Result<Token, AbiError> {
+        match typ {
+            ParamType::Address => self
+                .read_word(at)
+                .map(|word| Token::Address(Address::from_slice(&word[12..]))),
+            ParamType::Uint(_) => self.read_uint(at).map(Token::Uint),
+            ParamType::Int(_) => self.read_uint(at).map(Token::Int),
+            ParamType::Bool => match self.read_uint(at)? {
+                value if value.is_zero() => Ok(Token::Bool(false)),
+                value if value == U256::one() => Ok(Token::Bool(true)),
+                value => Err(AbiError::InvalidBool(value)),
+            },
+            ParamType::FixedBytes(len) => self
+                .read_word(at)
+                .map(|word| Token::FixedBytes(word[..*len].to_vec())),
+            ParamType::Bytes | ParamType::String => {
+                let len = self.read_usize(at)?;
+                let start = at + WORD;
+                let bytes = self
+                    .data
+                    .get(start..start + len)
+                    .ok_or(AbiError::TooShort {
+                        needed: start + len,
+                        len: self.data.len(),
+                    })?
+                    .to_vec();
+                self.charge(len.div_ceil(WORD))?;
+
+                if typ == &ParamType::String {
+                    String::from_utf8(bytes)
+                        .map(Token::String)
+                        .map_err(|_| AbiError::InvalidUtf8)
+                } else {
+                    Ok(Token::Bytes(bytes))
+                }
+            }
+            ParamType::Array(inner) => {
+                let len = self.read_usize(at)?;
+                // every element takes at least one word, which bounds the allocation
+                if len * WORD > self.data.len() {
+                    return Err(AbiError::OutOfBounds(U256::from(len)));
+                }
+
+                self.decode_params(std::iter::repeat_n(inner.as_ref(), len), at + WORD)
+                    .map(Token::Array)
+            }
+            ParamType::FixedArray(inner, len) => self
+                .decode_params(std::iter::repeat_n(inner.as_ref(), *len), at)
+                .map(Token::FixedArray),
+            ParamType::Tuple(types) => self.decode_params(types.iter(), at).map(Token::Tuple),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hex_words(words: &[&str]) -> Vec<u8> {
+        from_hex(&words.join("")).unwrap()
+    }
+
+    #[test]
+    fn selectors() {
+        let selectors = [
+            ("balanceOf(address)", "0x70a08231"),
+            ("decimals()", "0x313ce567"),
+            ("getEthBalance(address)", "0x4d2301cc"),
+            ("ownerOf(uint256)", "0x6352211e"),
+            ("aggregate((address,bytes)[])", "0x252dba42"),
+            ("balanceOfBatch(address[],uint256[])", "0x4e1273f4"),
+            ("transfer(address,uint256)", "0xa9059cbb"),
+        ];
+
+        for (signature, expected) in selectors {
+            assert_eq!(to_hex(&selector(signature)), expected);
+        }
+
+        let function = Function::parse("aggregate( (address, bytes)[] )").unwrap();
+
+        assert_eq!(function.signature(), "aggregate((address,bytes)[])");
+        assert_eq!(
+            Function::parse("balanceOf(uint)").unwrap().signature(),
+            "balanceOf(uint256)"
+        );
+        assert!(Function::parse("balanceOf(address").is_err());
+        assert!(Function::parse("balanceOf(uint7)").is_err());
+        assert!(Function::parse("(address)").is_err());
+    }
+
+    #[test]
+    fn param_types() {
+        let typ: ParamType = "(uint256[2],bytes32)[]".parse().unwrap();
+
+        assert_eq!(
+            typ,
+            ParamType::Array(Box::new(ParamType::Tuple(vec![
+                ParamType::FixedArray(Box::new(ParamType::Uint(256)), 2),
+                ParamType::FixedBytes(32),
+            ])))
+        );
+        assert_eq!(typ.to_string(), "(uint256[2],bytes32)[]");
+        assert!(typ.is_dynamic());
+        assert!(!"(uint8,address)[3]"
+            .parse::<ParamType>()
+            .unwrap()
+            .is_dynamic());
+        assert!("bytes33".parse::<ParamType>().is_err());
+        assert!("uint256[1024]".parse::<ParamType>().is_ok());
+        assert!("uint256[1025]".parse::<ParamType>().is_err());
+        assert!("uint256[2][1024]".parse::<ParamType>().is_err());
+        assert!("uint256[0]".parse::<ParamType>().is_err());
+        assert!("()[1]".parse::<ParamType>().is_err());
+    }
+
+    // https://docs.soliditylang.org/en/latest/abi-spec.html#use-of-dynamic-types
+    #[test]
+    fn static_and_dynamic_round_trip() {
+        let function = Function::parse("f(uint256,uint32[],bytes10,bytes)").unwrap();
+        let tokens = vec![
+            Token::Uint(U256::from(0x123)),
+            Token::Array(vec![
+                Token::Uint(U256::from(0x456)),
+                Token::Uint(U256::from(0x789)),
+            ]),
+            Token::FixedBytes(b"1234567890".to_vec()),
+            Token::Bytes(b"Hello, world!".to_vec()),
+        ];
+
+        let mut expected = from_hex("0x8be65246").unwrap();
+        expected.extend(hex_words(&[
+            "0000000000000000000000000000000000000000000000000000000000000123",
+            "0000000000000000000000000000000000000000000000000000000000000080",
+            "3132333435363738393000000000000000000000000000000000000000000000",
+            "00000000000000000000000000000000000000000000000000000000000000e0",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            "0000000000000000000000000000000000000000000000000000000000000456",
+            "0000000000000000000000000000000000000000000000000000000000000789",
+            "000000000000000000000000000000000000000000000000000000000000000d",
+            "48656c6c6f2c20776f726c642100000000000000000000000000000000000000",
+        ]));
+
+        let encoded = function.encode_input(&tokens);
+
+        assert_eq!(encoded, expected);
+        assert_eq!(decode(&function.inputs, &encoded[4..]).unwrap(), tokens);
+    }
+
+    #[test]
+    fn nested_dynamic_round_trip() {
+        let function = Function::parse("g(uint256[][],string[])").unwrap();
+        let uint = |value: u64| Token::Uint(U256::from(value));
+        let tokens = vec![
+            Token::Array(vec![
+                Token::Array(vec![uint(1), uint(2)]),
+                Token::Array(vec![uint(3)]),
+            ]),
+            Token::Array(vec![
+                Token::String("one".to_string()),
+                Token::String("two".to_string()),
+                Token::String("three".to_string()),
+            ]),
+        ];
+
+        let mut expected = from_hex("0x2289b18c").unwrap();
+        expected.extend(hex_words(&[
+            "0000000000000000000000000000000000000000000000000000000000000040",
+            "0000000000000000000000000000000000000000000000000000000000000140",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            "0000000000000000000000000000000000000000000000000000000000000040",
+            "00000000000000000000000000000000000000000000000000000000000000a0",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "0000000000000000000000000000000000000000000000000000000000000060",
+            "00000000000000000000000000000000000000000000000000000000000000a0",
+            "00000000000000000000000000000000000000000000000000000000000000e0",
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "6f6e650000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "74776f0000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000005",
+            "7468726565000000000000000000000000000000000000000000000000000000",
+        ]));
+
+        let encoded = function.encode_input(&tokens);
+
+        assert_eq!(encoded, expected);
+        assert_eq!(decode(&function.inputs, &encoded[4..]).unwrap(), tokens);
+    }
+
+    #[test]
+    fn malformed_data() {
+        let types = [ParamType::Bytes];
+
+        assert_eq!(
+            decode(&types, &[0u8; 16]),
+            Err(AbiError::TooShort {
+                needed: 32,
+                len: 16
+            })
+        );
+
+        let mut data = word(U256::from(32)).to_vec();
+        data.extend(word(U256::MAX));
+
+        assert_eq!(decode(&types, &data), Err(AbiError::OutOfBounds(U256::MAX)));
+
+        // both elements point at the same nested array
+        let nested = ParamType::Array(Box::new(ParamType::Array(Box::new(ParamType::Uint(256)))));
+        let data = [32, 2, 64, 64, 1, 7]
+            .into_iter()
+            .flat_map(|value| word(U256::from(value)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(decode(&[nested], &data), Err(AbiError::TooLarge(192)));
+        assert_eq!(
+            decode(&[ParamType::Bool], &word(U256::from(2))),
+            Err(AbiError::InvalidBool(U256::from(2)))
+        );
+        assert_eq!(
+            parse_address("0x458691c1692cd82facfb2c5127e36d63213448a8")
+                .map(|address| to_hex(address.as_bytes())),
+            Ok("0x458691c1692cd82facfb2c5127e36d63213448a8".to_string())
+        );
+        assert!(parse_address("458691c1692cd82facfb2c5127e36d63213448a8").is_err());
+    }
+}
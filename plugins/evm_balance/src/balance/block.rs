@@ -0,0 +1,202 @@
+use crate::balance::{EvmProvider, RpcError};
+use guild_common::BlockSpec;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::future::Future;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockId {
+    Latest,
+    Number(u64),
+    Hash(String),
+}
+
+impl BlockId {
+    // block parameter of eth_call, hashes are passed in the EIP-1898 form
+    pub fn to_param(&self) -> Value {
+        match self {
+            Self::Latest => json!("latest"),
+            Self::Number(number) => json!(format!("{number:#x}")),
+            Self::Hash(hash) => json!({ "blockHash": hash }),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BlockHeader {
+    number: String,
+    timestamp: String,
+}
+
+pub fn parse_quantity(quantity: &str) -> Result<u64, RpcError> {
+    quantity
+        .strip_prefix("0x")
+        .and_then(|digits| u64::from_str_radix(digits, 16).ok())
+        .ok_or_else(|| RpcError::Other(format!("{quantity} is not a valid quantity")))
+}
+
+fn block_request(block: &BlockId) -> (&'static str, Value) {
+    match block {
+        BlockId::Hash(hash) => ("eth_getBlockByHash", json!([hash, false])),
+        block => ("eth_getBlockByNumber", json!([block.to_param(), false])),
+    }
+}
+
+fn parse_header(block: &BlockId, header: Option<BlockHeader>) -> Result<(u64, u64), RpcError> {
+    let header = header.ok_or_else(|| RpcError::Other(format!("block {block:?} not found")))?;
+
+    Ok((
+        parse_quantity(&header.number)?,
+        parse_quantity(&header.timestamp)?,
+    ))
+}
+
+// returns the number and timestamp of the block
+pub async fn get_block(
+    client: &'static Client,
+    provider: &EvmProvider,
+    block: &BlockId,
+) -> Result<(u64, u64), RpcError> {
+    let (method, params) = block_request(block);
+    let header = provider.request(client, method, params).await?;
+
+    parse_header(block, header)
+}
+
+// fetches the timestamps of the blocks in JSON-RPC batches
+pub async fn get_block_timestamps(
+    client: &'static Client,
+    provider: &EvmProvider,
+    numbers: &[u64],
+) -> Result<Vec<u64>, RpcError> {
+    let blocks = numbers
+        .iter()
+        .map(|number| BlockId::Number(*number))
+        .collect::<Vec<_>>();
+    let requests = blocks.iter().map(block_request).collect::<Vec<_>>();
+
+    provider
+        .batch_request(client, &requests)
+        .await?
+        .into_iter()
+        .zip(&blocks)
+        .map(|(header, block)| parse_header(block, header?).map(|(_, timestamp)| timestamp))
+        .collect()
+}
+
+// binary search for the last block mined at or before the timestamp
+async fn find_block_by_timestamp<F, Fut>(
+    latest: u64,
+    timestamp: u64,
+    block_timestamp: F,
+) -> Result<u64, RpcError>
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Result<u64, RpcError>>,
+{
+    if block_timestamp(0).await? > timestamp {
+        return Err(RpcError::Other(format!(
+            "timestamp {timestamp} is before the first block"
+        )));
+    }
+
+    let (mut low, mut high) = (0, latest);
+
+    while low < high {
+        let mid = low + (high - low).div_ceil(2);
+
+        if block_timestamp(mid).await? <= timestamp {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    Ok(low)
+}
+
+// resolves the block every call of an evaluation is pinned to, along with
+// its number, so that "latest" can't move between calls
+pub async fn resolve_block(
+    client: &'static Client,
+    provider: &EvmProvider,
+    block: &BlockSpec,
+) -> Result<(BlockId, u64), RpcError> {
+    match block {
+        BlockSpec::Latest => {
+            let (latest, _) = get_block(client, provider, &BlockId::Latest).await?;
+
+            Ok((BlockId::Number(latest), latest))
+        }
+        BlockSpec::Number(number) => Ok((BlockId::Number(*number), *number)),
+        BlockSpec::Hash(hash) => {
+            let block = BlockId::Hash(hash.to_string());
+            let (number, _) = get_block(client, provider, &block).await?;
+
+            Ok((block, number))
+        }
+        BlockSpec::Timestamp(timestamp) => {
+            let (latest, latest_timestamp) = get_block(client, provider, &BlockId::Latest).await?;
+
+            if latest_timestamp <= *timestamp {
+                return Ok((BlockId::Number(latest), latest));
+            }
+
+            let number = find_block_by_timestamp(latest, *timestamp, |number| async move {
+                get_block(client, provider, &BlockId::Number(number))
+                    .await
+                    .map(|(_, timestamp)| timestamp)
+            })
+            .await?;
+
+            Ok((BlockId::Number(number), number))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_block_by_timestamp, BlockId, RpcError};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn block_params() {
+        assert_eq!(BlockId::Latest.to_param(), json!("latest"));
+        assert_eq!(BlockId::Number(17_000_000).to_param(), json!("0x1036640"));
+        assert_eq!(
+            BlockId::Hash("0xab".to_string()).to_param(),
+            json!({ "blockHash": "0xab" })
+        );
+    }
+
+    #[tokio::test]
+    async fn timestamp_search() {
+        // a block every 12 seconds starting at 1000, with a gap of 60
+        // seconds between blocks 99 and 100
+        let block_timestamp = |number: u64| 1000 + 12 * number + if number >= 100 { 48 } else { 0 };
+        let requests = AtomicUsize::new(0);
+        let fetch = |number: u64| {
+            requests.fetch_add(1, Ordering::Relaxed);
+            async move { Ok::<_, RpcError>(block_timestamp(number)) }
+        };
+
+        let search = |timestamp| find_block_by_timestamp(1_000_000, timestamp, fetch);
+
+        assert_eq!(search(1000).await.unwrap(), 0);
+        assert_eq!(search(1011).await.unwrap(), 0);
+        assert_eq!(search(1012).await.unwrap(), 1);
+        assert_eq!(search(block_timestamp(99)).await.unwrap(), 99);
+        assert_eq!(search(block_timestamp(100) - 1).await.unwrap(), 99);
+        assert_eq!(search(block_timestamp(100)).await.unwrap(), 100);
+        assert_eq!(search(block_timestamp(654_321) + 5).await.unwrap(), 654_321);
+        assert_eq!(search(u64::MAX).await.unwrap(), 1_000_000);
+        assert!(search(999).await.is_err());
+
+        // logarithmic in the number of blocks
+        requests.store(0, Ordering::Relaxed);
+        search(block_timestamp(123_456)).await.unwrap();
+        assert!(requests.load(Ordering::Relaxed) <= 22);
+    }
+}
@@ -1,82 +1,151 @@
 use crate::{
+    abi::{decode, encode_call, from_hex, parse_address, to_hex, ParamType, Token},
     balance::{
         contract::multicall::{aggregate, parse_multicall_result},
-        create_payload, RpcError, RpcResponse, ETH_BALANCE_DIVIDER,
+        BlockId, EvmProvider, RpcError, ETH_DECIMALS,
     },
-    rpc_error,
 };
-use guild_common::Scalar;
-use primitive_types::U256;
+use guild_common::{scalar_from_units, BalanceMode, Scalar};
+use primitive_types::{H160 as Address, U256};
 use reqwest::Client;
-use serde_json::json;
-use std::str::FromStr;
+use serde_json::{json, Value};
+use std::collections::HashMap;
 
 mod multicall;
 
-const FUNC_DECIMALS: &str = "313ce567";
-const FUNC_ETH_BALANCE: &str = "4d2301cc";
-const FUNC_BALANCE_OF: &str = "70a08231";
-const FUNC_OWNER_OF: &str = "6352211e";
-const FUNC_ERC1155_BATCH: &str = "4e1273f4";
+pub use multicall::MulticallKind;
+
+const FUNC_DECIMALS: &str = "decimals()";
+// a uint256 has 78 digits, more decimals than that can't be a real token
+const MAX_DECIMALS: u32 = 77;
+const FUNC_ETH_BALANCE: &str = "getEthBalance(address)";
+const FUNC_BALANCE_OF: &str = "balanceOf(address)";
+const FUNC_OWNER_OF: &str = "ownerOf(uint256)";
+const FUNC_ERC1155_BATCH: &str = "balanceOfBatch(address[],uint256[])";
+
+fn to_scalar(amount: &U256, decimals: u32) -> Scalar {
+    scalar_from_units(&amount.to_string(), decimals).unwrap_or_default()
+}
 
 #[derive(Clone, Debug)]
 pub struct Call {
-    pub target: String,
-    pub call_data: String,
+    pub target: Address,
+    pub call_data: Vec<u8>,
+}
+
+pub fn parse_addresses(addresses: &[&str]) -> Result<Vec<Address>, RpcError> {
+    addresses
+        .iter()
+        .map(|address| parse_address(address).map_err(RpcError::from))
+        .collect()
+}
+
+fn decode_uint(data: &[u8]) -> Result<U256, RpcError> {
+    decode(&[ParamType::Uint(256)], data)?
+        .pop()
+        .and_then(Token::into_uint)
+        .ok_or_else(|| RpcError::Other("expected a uint256 return value".to_string()))
+}
+
+fn call_params(call: &Call, block: &BlockId) -> Value {
+    json!([
+        {
+            "to"   : to_hex(call.target.as_bytes()),
+            "data" : to_hex(&call.call_data)
+        },
+        block.to_param()
+    ])
 }
 
 async fn call_contract(
     client: &'static Client,
-    rpc_url: &str,
+    provider: &EvmProvider,
     call: Call,
-) -> Result<String, RpcError> {
-    let params = json!([
-        {
-            "to"   : call.target,
-            "data" : format!("0x{}", call.call_data)
-        },
-        "latest"
-    ]);
+    block: &BlockId,
+) -> Result<Vec<u8>, RpcError> {
+    let res: String = provider
+        .request(client, "eth_call", call_params(&call, block))
+        .await?;
 
-    let payload = create_payload("eth_call", params, 1);
+    Ok(from_hex(&res)?)
+}
+
+// sends the calls as JSON-RPC batches, reverted calls have no data
+async fn call_contracts(
+    client: &'static Client,
+    provider: &EvmProvider,
+    calls: &[Call],
+    block: &BlockId,
+) -> Result<Vec<Option<Vec<u8>>>, RpcError> {
+    let requests = calls
+        .iter()
+        .map(|call| ("eth_call", call_params(call, block)))
+        .collect::<Vec<_>>();
 
-    let res: RpcResponse = client
-        .post(rpc_url)
-        .json(&payload)
-        .send()
+    provider
+        .batch_request::<String>(client, &requests)
         .await?
-        .json()
-        .await?;
+        .into_iter()
+        .map(|res| match res {
+            Ok(data) => Ok(Some(from_hex(&data)?)),
+            Err(RpcError::JsonRpc(err)) if err.is_revert() => Ok(None),
+            Err(err) => Err(err),
+        })
+        .collect()
+}
 
-    Ok(res.result)
+// returns the data of each call, or None if the call failed
+pub async fn aggregate_calls(
+    client: &'static Client,
+    provider: &EvmProvider,
+    calls: &[Call],
+    block: &BlockId,
+) -> Result<Vec<Option<Vec<u8>>>, RpcError> {
+    let call = Call {
+        target: parse_address(&provider.contract)?,
+        call_data: aggregate(provider.multicall, calls),
+    };
+
+    let res = call_contract(client, provider, call, block).await?;
+
+    parse_multicall_result(provider.multicall, &res)
+}
+
+// calls that failed or returned something other than a uint256 are unknown
+async fn aggregate_uints(
+    client: &'static Client,
+    provider: &EvmProvider,
+    calls: &[Call],
+    block: &BlockId,
+) -> Result<Vec<Option<U256>>, RpcError> {
+    let values = aggregate_calls(client, provider, calls, block)
+        .await?
+        .into_iter()
+        .map(|data| data.and_then(|data| decode_uint(&data).ok()))
+        .collect();
+
+    Ok(values)
 }
 
 pub async fn get_eth_balance_batch(
     client: &'static Client,
-    multicall_address: &str,
-    rpc_url: &str,
+    provider: &EvmProvider,
     user_addresses: &[&str],
-) -> Result<Vec<Scalar>, RpcError> {
-    let calls = user_addresses
-        .iter()
+    block: &BlockId,
+) -> Result<Vec<Option<Scalar>>, RpcError> {
+    let multicall = parse_address(&provider.contract)?;
+    let calls = parse_addresses(user_addresses)?
+        .into_iter()
         .map(|address| Call {
-            target: multicall_address.to_string(),
-            call_data: format!(
-                "{FUNC_ETH_BALANCE}{:0>64}",
-                address.trim_start_matches("0x")
-            ),
+            target: multicall,
+            call_data: encode_call(FUNC_ETH_BALANCE, &[Token::Address(address)]),
         })
         .collect::<Vec<Call>>();
 
-    let call = Call {
-        target: multicall_address.to_string(),
-        call_data: aggregate(&calls),
-    };
-
-    let res = call_contract(client, rpc_url, call).await?;
-    let balances = parse_multicall_result(&res)?
+    let balances = aggregate_uints(client, provider, &calls, block)
+        .await?
         .iter()
-        .map(|balance| balance / ETH_BALANCE_DIVIDER)
+        .map(|balance| balance.map(|balance| to_scalar(&balance, ETH_DECIMALS)))
         .collect();
 
     Ok(balances)
@@ -84,170 +153,379 @@ pub async fn get_eth_balance_batch(
 
 pub async fn get_erc20_decimals(
     client: &'static Client,
-    rpc_url: &str,
+    provider: &EvmProvider,
     token_address: &str,
+    block: &BlockId,
 ) -> Result<u32, RpcError> {
     let call = Call {
-        target: token_address.to_string(),
-        call_data: FUNC_DECIMALS.to_string(),
+        target: parse_address(token_address)?,
+        call_data: encode_call(FUNC_DECIMALS, &[]),
     };
-    let decimals = call_contract(client, rpc_url, call).await?;
+    let decimals = call_contract(client, provider, call, block).await?;
 
-    Ok(rpc_error!(U256::from_str(&decimals))?.as_u32())
+    check_decimals(decode_uint(&decimals)?)
 }
 
-fn erc20_call(token_address: &str, user_address: &str) -> Call {
+fn check_decimals(decimals: U256) -> Result<u32, RpcError> {
+    u32::try_from(decimals)
+        .ok()
+        .filter(|decimals| *decimals <= MAX_DECIMALS)
+        .ok_or_else(|| RpcError::Other(format!("Invalid token decimals {decimals}")))
+}
+
+fn erc20_call(token_address: Address, user_address: Address) -> Call {
     Call {
-        target: token_address.to_string(),
-        call_data: format!(
-            "{FUNC_BALANCE_OF}{:0>64}",
-            user_address.trim_start_matches("0x")
-        ),
+        target: token_address,
+        call_data: encode_call(FUNC_BALANCE_OF, &[Token::Address(user_address)]),
     }
 }
 
 pub async fn get_erc20_balance_batch(
     client: &'static Client,
-    multicall_address: &str,
-    rpc_url: &str,
+    provider: &EvmProvider,
     token_address: &str,
+    decimals: u32,
     user_addresses: &[&str],
-) -> Result<Vec<Scalar>, RpcError> {
-    let calls = user_addresses
-        .iter()
-        .map(|user_address| erc20_call(token_address, user_address))
+    block: &BlockId,
+) -> Result<Vec<Option<Scalar>>, RpcError> {
+    let token = parse_address(token_address)?;
+    let calls = parse_addresses(user_addresses)?
+        .into_iter()
+        .map(|user_address| erc20_call(token, user_address))
         .collect::<Vec<Call>>();
 
-    let call = Call {
-        target: multicall_address.to_string(),
-        call_data: aggregate(&calls),
-    };
-
-    let res = call_contract(client, rpc_url, call).await?;
-    let decimals = get_erc20_decimals(client, rpc_url, token_address).await?;
+    let balances = aggregate_uints(client, provider, &calls, block).await?;
 
-    let balances = parse_multicall_result(&res)?
+    let balances = balances
         .iter()
-        .map(|balance| balance / 10_u128.pow(decimals) as Scalar)
+        .map(|balance| balance.map(|balance| to_scalar(&balance, decimals)))
         .collect();
 
     Ok(balances)
 }
 
-pub fn erc721_call(token_address: &str, user_address: &str) -> Call {
+pub fn erc721_call(token_address: Address, user_address: Address) -> Call {
     erc20_call(token_address, user_address)
 }
 
-fn erc721_id_call(token_address: &str, id: &str) -> Call {
+fn erc721_id_call(token_address: Address, id: U256) -> Call {
     Call {
-        target: token_address.to_string(),
-        call_data: format!("{FUNC_OWNER_OF}{id:0>64}"),
+        target: token_address,
+        call_data: encode_call(FUNC_OWNER_OF, &[Token::Uint(id)]),
     }
 }
 
 pub async fn get_erc721_balance_batch(
     client: &'static Client,
-    multicall_address: &str,
-    rpc_url: &str,
+    provider: &EvmProvider,
     token_address: &str,
-    token_id: &Option<String>,
     user_addresses: &[&str],
-) -> Result<Vec<Scalar>, RpcError> {
-    let res = match token_id {
-        Some(id) => {
-            let id = format!("{:x}", rpc_error!(U256::from_dec_str(id))?);
-            let call = erc721_id_call(token_address, &id);
-            let address = call_contract(client, rpc_url, call).await?;
-            let trimmed = format!("0x{}", &address[26..]);
-
-            user_addresses
-                .iter()
-                .map(|addr| (addr.to_lowercase() == trimmed) as u8 as Scalar)
-                .collect::<Vec<Scalar>>()
-        }
-        None => {
-            let calls = user_addresses
-                .iter()
-                .map(|user_address| erc721_call(token_address, user_address))
-                .collect::<Vec<Call>>();
+    block: &BlockId,
+) -> Result<Vec<Option<Scalar>>, RpcError> {
+    let token = parse_address(token_address)?;
+    let calls = parse_addresses(user_addresses)?
+        .into_iter()
+        .map(|user_address| erc721_call(token, user_address))
+        .collect::<Vec<Call>>();
+
+    let balances = aggregate_uints(client, provider, &calls, block)
+        .await?
+        .iter()
+        .map(|balance| balance.map(|balance| to_scalar(&balance, 0)))
+        .collect();
+
+    Ok(balances)
+}
 
-            let call = Call {
-                target: multicall_address.to_string(),
-                call_data: aggregate(&calls),
-            };
+fn decode_owner(data: &[u8]) -> Option<Address> {
+    decode(&[ParamType::Address], data)
+        .ok()?
+        .pop()?
+        .into_address()
+        .filter(|owner| !owner.is_zero())
+}
 
-            let res = call_contract(client, rpc_url, call).await?;
+// ownerOf reverts for burned and nonexistent tokens, those have no owner
+pub async fn get_erc721_owners(
+    client: &'static Client,
+    provider: &EvmProvider,
+    token_address: &str,
+    token_ids: &[U256],
+    block: &BlockId,
+) -> Result<Vec<Option<Address>>, RpcError> {
+    let token = parse_address(token_address)?;
+    let calls = token_ids
+        .iter()
+        .map(|id| erc721_id_call(token, *id))
+        .collect::<Vec<Call>>();
 
-            parse_multicall_result(&res)?
+    let results = match aggregate_calls(client, provider, &calls, block).await {
+        Ok(results) => results,
+        // the legacy aggregate reverts as a whole, so ask for each id separately
+        Err(RpcError::JsonRpc(err))
+            if err.is_revert() && provider.multicall == MulticallKind::Aggregate =>
+        {
+            call_contracts(client, provider, &calls, block).await?
         }
+        Err(err) => return Err(err),
     };
 
-    Ok(res)
+    let owners = results
+        .iter()
+        .map(|data| data.as_deref().and_then(decode_owner))
+        .collect();
+
+    Ok(owners)
 }
 
-pub async fn get_erc1155_balance_batch(
-    client: &'static Client,
-    rpc_url: &str,
-    token_address: String,
-    token_id: &str,
+// the number of tokens each address owns
+pub fn count_owned(
+    owners: &[Option<Address>],
     user_addresses: &[&str],
-) -> Result<Vec<Scalar>, RpcError> {
-    let id = format!("{:x}", rpc_error!(U256::from_dec_str(token_id))?);
-    let addresses = user_addresses
+) -> Result<Vec<Option<Scalar>>, RpcError> {
+    let mut counts = HashMap::<Address, u64>::new();
+
+    for owner in owners.iter().flatten() {
+        *counts.entry(*owner).or_default() += 1;
+    }
+
+    let counts = parse_addresses(user_addresses)?
         .iter()
-        .map(|user_address| format!("{:0>64}", user_address.trim_start_matches("0x")))
-        .collect::<String>();
+        .map(|user| Some(Scalar::from(counts.get(user).copied().unwrap_or_default())))
+        .collect();
+
+    Ok(counts)
+}
 
-    let len = 64;
-    let count = user_addresses.len();
-    let offset = (count + 3) * 32;
-    let ids = vec![format!("{id:0>64}"); count].join("");
+pub fn decode_uints(data: &[u8]) -> Result<Vec<U256>, RpcError> {
+    decode(&[ParamType::Array(Box::new(ParamType::Uint(256)))], data)?
+        .pop()
+        .and_then(Token::into_array)
+        .ok_or_else(|| RpcError::Other("expected a uint256[] return value".to_string()))?
+        .into_iter()
+        .map(|value| {
+            value
+                .into_uint()
+                .ok_or_else(|| RpcError::Other("expected a uint256 value".to_string()))
+        })
+        .collect()
+}
 
-    let call_data = format!(
-        "{FUNC_ERC1155_BATCH}{len:064x}{offset:064x}{count:064x}{addresses}{count:064x}{ids}",
-    );
+// balanceOfBatch returns the balance of each (account, id) pair
+pub async fn get_erc1155_balance_batch(
+    client: &'static Client,
+    provider: &EvmProvider,
+    token_address: &str,
+    pairs: &[(Address, U256)],
+    block: &BlockId,
+) -> Result<Vec<U256>, RpcError> {
+    let (accounts, ids): (Vec<Token>, Vec<Token>) = pairs
+        .iter()
+        .map(|(account, id)| (Token::Address(*account), Token::Uint(*id)))
+        .unzip();
 
     let call = Call {
-        target: token_address,
-        call_data,
+        target: parse_address(token_address)?,
+        call_data: encode_call(
+            FUNC_ERC1155_BATCH,
+            &[Token::Array(accounts), Token::Array(ids)],
+        ),
     };
 
-    let res = call_contract(client, rpc_url, call).await?;
+    let res = call_contract(client, provider, call, block).await?;
 
-    let balances = res
-        .trim_start_matches("0x")
-        .chars()
-        .collect::<Vec<char>>()
-        .chunks(64)
-        .skip(2)
-        .map(|c| {
-            let balance = c.iter().collect::<String>();
+    decode_uints(&res)
+}
 
-            rpc_error!(U256::from_str(&balance).map(|value| value.as_u128() as Scalar))
+// splits the balances of the (account, id) pairs back to the accounts
+// the balances of the ids queried for each account, per id values cover every
+// requested id, the ones that weren't queried are zero
+pub fn group_erc1155_balances(
+    balances: &[U256],
+    ids_per_account: &[Vec<U256>],
+    requested: Option<&[U256]>,
+    mode: BalanceMode,
+) -> Vec<Vec<Option<Scalar>>> {
+    let mut balances = balances.iter();
+
+    ids_per_account
+        .iter()
+        .map(|ids| {
+            let balances = ids.iter().zip(balances.by_ref()).collect::<HashMap<_, _>>();
+
+            match (mode, requested) {
+                (BalanceMode::Sum, _) => {
+                    vec![Some(
+                        balances.values().map(|balance| to_scalar(balance, 0)).sum(),
+                    )]
+                }
+                (BalanceMode::PerId, Some(requested)) => requested
+                    .iter()
+                    .map(|id| {
+                        let balance = balances.get(id).copied().copied().unwrap_or_default();
+                        Some(to_scalar(&balance, 0))
+                    })
+                    .collect(),
+                (BalanceMode::PerId, None) => ids
+                    .iter()
+                    .map(|id| Some(to_scalar(balances[id], 0)))
+                    .collect(),
+            }
         })
-        .collect::<Vec<Result<Scalar, RpcError>>>();
-
-    balances.into_iter().collect()
+        .collect()
 }
 
 #[cfg(test)]
 mod test {
-    use crate::balance::{common::*, get_erc20_decimals};
+    use super::{check_decimals, decode_owner, decode_uints, group_erc1155_balances};
+    use crate::{
+        abi::{encode, parse_address, Token},
+        balance::{common::*, count_owned, get_erc20_decimals, BlockId, EvmProvider},
+    };
+    use guild_common::{BalanceMode, Scalar};
+    use primitive_types::{H160 as Address, U256};
     use reqwest::Client;
 
+    #[test]
+    fn erc721_owners() {
+        let user_1 = parse_address(USER_1_ADDR).unwrap();
+        let user_3 = parse_address(USER_3_ADDR).unwrap();
+
+        assert_eq!(
+            decode_owner(&encode(&[Token::Address(user_1)])),
+            Some(user_1)
+        );
+        assert_eq!(
+            decode_owner(&encode(&[Token::Address(Address::zero())])),
+            None
+        );
+        assert_eq!(decode_owner(&[]), None);
+        assert_eq!(decode_owner(&[0; 12]), None);
+
+        let owners = [Some(user_1), None, Some(user_3), Some(user_1)];
+
+        assert_eq!(
+            count_owned(&owners, &[USER_1_ADDR, USER_2_ADDR, USER_3_ADDR]).unwrap(),
+            vec![
+                Some(Scalar::from(2)),
+                Some(Scalar::from(0)),
+                Some(Scalar::from(1))
+            ]
+        );
+    }
+
+    #[test]
+    fn erc1155_balances() {
+        // balanceOfBatch returning [0, 15, 3]
+        let data = [
+            "0000000000000000000000000000000000000000000000000000000000000020",
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "000000000000000000000000000000000000000000000000000000000000000f",
+            "0000000000000000000000000000000000000000000000000000000000000003",
+        ]
+        .join("");
+        let balances = decode_uints(&crate::abi::from_hex(&data).unwrap()).unwrap();
+
+        assert_eq!(balances, [U256::zero(), U256::from(15), U256::from(3)]);
+        assert!(decode_uints(&[0; 31]).is_err());
+
+        let ids_per_account = [
+            vec![U256::from(1), U256::from(2)],
+            vec![],
+            vec![U256::from(7)],
+        ];
+
+        assert_eq!(
+            group_erc1155_balances(&balances, &ids_per_account, None, BalanceMode::Sum),
+            vec![
+                vec![Some(Scalar::from(15))],
+                vec![Some(Scalar::from(0))],
+                vec![Some(Scalar::from(3))]
+            ]
+        );
+        assert_eq!(
+            group_erc1155_balances(&balances, &ids_per_account, None, BalanceMode::PerId),
+            vec![
+                vec![Some(Scalar::from(0)), Some(Scalar::from(15))],
+                vec![],
+                vec![Some(Scalar::from(3))]
+            ]
+        );
+
+        // ids that weren't queried for an account are zero
+        let requested = [U256::from(1), U256::from(2), U256::from(7)];
+
+        assert_eq!(
+            group_erc1155_balances(
+                &balances,
+                &ids_per_account,
+                Some(&requested),
+                BalanceMode::PerId
+            ),
+            vec![
+                vec![
+                    Some(Scalar::from(0)),
+                    Some(Scalar::from(15)),
+                    Some(Scalar::from(0))
+                ],
+                vec![Some(Scalar::from(0)); 3],
+                vec![
+                    Some(Scalar::from(0)),
+                    Some(Scalar::from(0)),
+                    Some(Scalar::from(3))
+                ]
+            ]
+        );
+        assert_eq!(
+            group_erc1155_balances(
+                &balances,
+                &ids_per_account,
+                Some(&requested),
+                BalanceMode::Sum
+            ),
+            vec![
+                vec![Some(Scalar::from(15))],
+                vec![Some(Scalar::from(0))],
+                vec![Some(Scalar::from(3))]
+            ]
+        );
+    }
+
+    #[test]
+    fn erc20_decimals() {
+        assert_eq!(check_decimals(U256::from(18)).unwrap(), 18);
+        assert_eq!(check_decimals(U256::from(77)).unwrap(), 77);
+        assert!(check_decimals(U256::from(78)).is_err());
+        assert!(check_decimals(U256::from(u64::MAX)).is_err());
+        assert!(check_decimals(U256::MAX).is_err());
+    }
+
     #[tokio::test]
     async fn rpc_get_erc20_decimals() {
         let client: &'static Client = Box::leak(Box::new(Client::new()));
+        let provider: EvmProvider = serde_json::from_str(&format!(
+            r#"{{"rpc_url":"{RPC_URL}","contract":"0xcA11bde05977b3631167028862bE2a173976CA11"}}"#
+        ))
+        .unwrap();
 
         let token_1 = ERC20_ADDR;
         let token_2 = "0x343e59d9d835e35b07fe80f5bb544f8ed1cd3b11";
         let token_3 = "0xaba8cac6866b83ae4eec97dd07ed254282f6ad8a";
         let token_4 = "0x0a9f693fce6f00a51a8e0db4351b5a8078b4242e";
 
-        let decimals_1 = get_erc20_decimals(client, RPC_URL, token_1).await.unwrap();
-        let decimals_2 = get_erc20_decimals(client, RPC_URL, token_2).await.unwrap();
-        let decimals_3 = get_erc20_decimals(client, RPC_URL, token_3).await.unwrap();
-        let decimals_4 = get_erc20_decimals(client, RPC_URL, token_4).await.unwrap();
+        let decimals_1 = get_erc20_decimals(client, &provider, token_1, &BlockId::Latest)
+            .await
+            .unwrap();
+        let decimals_2 = get_erc20_decimals(client, &provider, token_2, &BlockId::Latest)
+            .await
+            .unwrap();
+        let decimals_3 = get_erc20_decimals(client, &provider, token_3, &BlockId::Latest)
+            .await
+            .unwrap();
+        let decimals_4 = get_erc20_decimals(client, &provider, token_4, &BlockId::Latest)
+            .await
+            .unwrap();
 
         assert_eq!(decimals_1, 18);
         assert_eq!(decimals_2, 9);
@@ -1,65 +1,122 @@
 use crate::{
+    abi::{decode, encode_call, ParamType, Token},
     balance::contract::{Call, RpcError},
-    rpc_error,
 };
-use guild_common::Scalar;
-use primitive_types::U256;
-use std::str::FromStr;
+use serde::Deserialize;
 
-const FUNC_SIG: &str = "252dba42";
-const PARAM_COUNT_LEN: usize = 32;
-const DATA_PART_LEN: usize = 64;
+const FUNC_AGGREGATE: &str = "aggregate((address,bytes)[])";
+const FUNC_TRY_AGGREGATE: &str = "tryAggregate(bool,(address,bytes)[])";
+const FUNC_AGGREGATE3: &str = "aggregate3((address,bool,bytes)[])";
 
-pub fn aggregate(calls: &[Call]) -> String {
-    let param_count_len = format!("{PARAM_COUNT_LEN:064x}");
-    let param_count = format!("{:064x}", calls.len());
+// the legacy aggregate reverts the whole batch when a single call reverts,
+// tryAggregate (Multicall2/3) and aggregate3 (Multicall3) report it per call.
+// aggregate3 is the default, so a contract that is not Multicall3 has to set
+// the kind it supports
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MulticallKind {
+    Aggregate,
+    TryAggregate,
+    #[default]
+    Aggregate3,
+}
 
-    let aggregated = calls
+pub fn aggregate(kind: MulticallKind, calls: &[Call]) -> Vec<u8> {
+    let calls = calls
         .iter()
         .map(|call| {
-            let data_len = call.call_data.len() / 2;
-            let padding = vec!["0"; (DATA_PART_LEN - data_len) * 2].join("");
-
-            format!(
-                "{:0>64}{DATA_PART_LEN:064x}{data_len:064x}{}{padding}",
-                call.target.trim_start_matches("0x"),
-                call.call_data.trim_start_matches("0x")
-            )
+            let target = Token::Address(call.target);
+            let call_data = Token::Bytes(call.call_data.clone());
+
+            match kind {
+                MulticallKind::Aggregate3 => {
+                    Token::Tuple(vec![target, Token::Bool(true), call_data])
+                }
+                _ => Token::Tuple(vec![target, call_data]),
+            }
         })
-        .collect::<String>();
+        .collect();
 
-    let offset = (0..(calls.len() * 5))
-        .step_by(5)
-        .map(|idx| format!("{:064x}", (idx + calls.len()) * 32))
-        .collect::<String>();
+    match kind {
+        MulticallKind::Aggregate => encode_call(FUNC_AGGREGATE, &[Token::Array(calls)]),
+        MulticallKind::TryAggregate => encode_call(
+            FUNC_TRY_AGGREGATE,
+            &[Token::Bool(false), Token::Array(calls)],
+        ),
+        MulticallKind::Aggregate3 => encode_call(FUNC_AGGREGATE3, &[Token::Array(calls)]),
+    }
+}
 
-    format!("{FUNC_SIG}{param_count_len}{param_count}{offset}{aggregated}")
+fn invalid_result(expected: &str) -> RpcError {
+    RpcError::Other(format!("invalid multicall result: expected {expected}"))
 }
 
-pub fn parse_multicall_result(multicall_result: &str) -> Result<Vec<Scalar>, RpcError> {
-    let lines = multicall_result
-        .trim_start_matches("0x")
-        .chars()
-        .collect::<Vec<char>>()
-        .chunks(64)
-        .map(|c| c.iter().collect::<String>())
-        .collect::<Vec<String>>();
-
-    let count = rpc_error!(U256::from_str(&lines[2]))?.as_usize();
-
-    let balances = lines
-        .into_iter()
-        .skip(count + 4)
-        .step_by(2)
-        .map(|balance| rpc_error!(U256::from_str(&balance).map(|value| value.as_u128() as Scalar)))
-        .collect::<Vec<Result<Scalar, RpcError>>>();
-
-    balances.into_iter().collect()
+// returns the data of each call, or None if the call failed
+pub fn parse_multicall_result(
+    kind: MulticallKind,
+    multicall_result: &[u8],
+) -> Result<Vec<Option<Vec<u8>>>, RpcError> {
+    match kind {
+        // aggregate returns (uint256 blockNumber, bytes[] returnData)
+        MulticallKind::Aggregate => {
+            let types = [
+                ParamType::Uint(256),
+                ParamType::Array(Box::new(ParamType::Bytes)),
+            ];
+
+            decode(&types, multicall_result)?
+                .pop()
+                .and_then(Token::into_array)
+                .ok_or_else(|| invalid_result("bytes[]"))?
+                .into_iter()
+                .map(|data| {
+                    data.into_bytes()
+                        .map(Some)
+                        .ok_or_else(|| invalid_result("bytes"))
+                })
+                .collect()
+        }
+        // the others return (bool success, bytes returnData)[]
+        MulticallKind::TryAggregate | MulticallKind::Aggregate3 => {
+            let types = [ParamType::Array(Box::new(ParamType::Tuple(vec![
+                ParamType::Bool,
+                ParamType::Bytes,
+            ])))];
+
+            decode(&types, multicall_result)?
+                .pop()
+                .and_then(Token::into_array)
+                .ok_or_else(|| invalid_result("(bool,bytes)[]"))?
+                .into_iter()
+                .map(|result| {
+                    let mut result = result
+                        .into_tuple()
+                        .ok_or_else(|| invalid_result("(bool,bytes)"))?
+                        .into_iter();
+
+                    match (
+                        result.next().and_then(Token::into_bool),
+                        result.next().and_then(Token::into_bytes),
+                    ) {
+                        (Some(success), Some(data)) => Ok(success.then_some(data)),
+                        _ => Err(invalid_result("(bool,bytes)")),
+                    }
+                })
+                .collect()
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::balance::contract::{erc20_call, multicall::aggregate};
+    use crate::{
+        abi::{encode, from_hex, parse_address, Token},
+        balance::contract::{
+            erc20_call,
+            multicall::{aggregate, parse_multicall_result, MulticallKind},
+        },
+    };
+    use primitive_types::U256;
 
     #[test]
     fn aggregate_test() {
@@ -98,13 +155,59 @@ mod test {
         ]
         .join("");
 
-        let erc20_addr = "0x458691c1692cd82facfb2c5127e36d63213448a8";
-        let user1_addr = "0xe43878ce78934fe8007748ff481f03b8ee3b97de";
-        let user2_addr = "0x14ddfe8ea7ffc338015627d160ccaf99e8f16dd3";
+        let erc20_addr = parse_address("0x458691c1692cd82facfb2c5127e36d63213448a8").unwrap();
+        let user1_addr = parse_address("0xe43878ce78934fe8007748ff481f03b8ee3b97de").unwrap();
+        let user2_addr = parse_address("0x14ddfe8ea7ffc338015627d160ccaf99e8f16dd3").unwrap();
+
+        let calls = [
+            erc20_call(erc20_addr, user1_addr),
+            erc20_call(erc20_addr, user2_addr),
+        ];
+
+        assert_eq!(
+            aggregate(MulticallKind::Aggregate, &calls),
+            from_hex(&data).unwrap()
+        );
+        assert_eq!(MulticallKind::default(), MulticallKind::Aggregate3);
+
+        let try_aggregate = aggregate(MulticallKind::TryAggregate, &calls[..1]);
+        let aggregate3 = aggregate(MulticallKind::Aggregate3, &calls[..1]);
+
+        assert_eq!(try_aggregate[..4], [0xbc, 0xe3, 0x8b, 0xd7]);
+        // requireSuccess = false
+        assert_eq!(try_aggregate[4..36], [0u8; 32]);
+        assert_eq!(aggregate3[..4], [0x82, 0xad, 0x56, 0xcb]);
+    }
+
+    #[test]
+    fn parse_multicall_result_test() {
+        let balance = |value: u64| encode(&[Token::Uint(U256::from(value))]);
+        let result = encode(&[
+            Token::Uint(U256::from(17_000_000)),
+            Token::Array(vec![
+                Token::Bytes(balance(5)),
+                Token::Bytes(balance(0)),
+                Token::Bytes(balance(7)),
+            ]),
+        ]);
+
+        assert_eq!(
+            parse_multicall_result(MulticallKind::Aggregate, &result).unwrap(),
+            [Some(balance(5)), Some(balance(0)), Some(balance(7))]
+        );
+        assert!(parse_multicall_result(MulticallKind::Aggregate, &result[..100]).is_err());
 
-        let call_1 = erc20_call(erc20_addr, user1_addr);
-        let call_2 = erc20_call(erc20_addr, user2_addr);
+        let result = encode(&[Token::Array(vec![
+            Token::Tuple(vec![Token::Bool(true), Token::Bytes(balance(5))]),
+            Token::Tuple(vec![Token::Bool(false), Token::Bytes(vec![])]),
+            Token::Tuple(vec![Token::Bool(true), Token::Bytes(balance(7))]),
+        ])]);
 
-        assert_eq!(aggregate(&[call_1, call_2]), data);
+        for kind in [MulticallKind::TryAggregate, MulticallKind::Aggregate3] {
+            assert_eq!(
+                parse_multicall_result(kind, &result).unwrap(),
+                [Some(balance(5)), None, Some(balance(7))]
+            );
+        }
     }
 }
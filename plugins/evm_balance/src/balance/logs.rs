@@ -0,0 +1,405 @@
+use crate::{
+    abi::{decode, encode, from_hex, keccak256, to_hex, ParamType, Token},
+    balance::{parse_quantity, EvmProvider, RpcError},
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use primitive_types::{H160 as Address, U256};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeSet, HashMap},
+    future::Future,
+};
+
+const TRANSFER_SINGLE: &str = "TransferSingle(address,address,address,uint256,uint256)";
+const TRANSFER_BATCH: &str = "TransferBatch(address,address,address,uint256[],uint256[])";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Log {
+    pub topics: Vec<String>,
+    pub data: String,
+    pub block_number: String,
+    pub log_index: String,
+}
+
+impl Log {
+    // the block number and log index, logs are ordered by them
+    pub fn position(&self) -> Result<(u64, u64), RpcError> {
+        Ok((
+            parse_quantity(&self.block_number)?,
+            parse_quantity(&self.log_index)?,
+        ))
+    }
+}
+
+pub fn event_topic(signature: &str) -> String {
+    to_hex(&keccak256(signature.as_bytes()))
+}
+
+pub fn address_topic(address: &Address) -> String {
+    to_hex(&encode(&[Token::Address(*address)]))
+}
+
+pub fn topic_address(topic: &str) -> Result<Address, RpcError> {
+    decode(&[ParamType::Address], &from_hex(topic)?)?
+        .pop()
+        .and_then(Token::into_address)
+        .ok_or_else(|| RpcError::Other(format!("invalid address topic {topic}")))
+}
+
+// whether the node refused an eth_getLogs query for spanning too many blocks
+// or results, only a smaller range can succeed then. the code isn't enough
+// since -32005 also means rate limited on some nodes
+fn is_range_limit(err: &RpcError) -> bool {
+    let RpcError::JsonRpc(err) = err else {
+        return false;
+    };
+    let message = err.message.to_ascii_lowercase();
+
+    [
+        "query returned more than",
+        "response size exceeded",
+        "block range",
+        "range is too",
+        "is limited to a",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+// rejected ranges are left to the pagination to shrink instead of being
+// retried at every endpoint
+async fn get_logs(
+    client: &'static Client,
+    provider: &EvmProvider,
+    filter: Value,
+) -> Result<Vec<Log>, RpcError> {
+    provider
+        .request_with(client, "eth_getLogs", json!([filter]), |err| {
+            err.is_retryable() && !is_range_limit(err)
+        })
+        .await
+}
+
+// successful pages in a row before a shrunk page is doubled again
+const PAGE_GROWTH_STREAK: u32 = 4;
+
+// walks the block range in pages of at most `max_page` blocks, halving the
+// page whenever the node rejects its range and only growing it back after a
+// streak of successes, other errors are returned right away
+async fn paginate<F, Fut>(
+    from_block: u64,
+    to_block: u64,
+    max_page: u64,
+    fetch: F,
+) -> Result<Vec<Log>, RpcError>
+where
+    F: Fn(u64, u64) -> Fut,
+    Fut: Future<Output = Result<Vec<Log>, RpcError>>,
+{
+    let mut logs = vec![];
+    let mut page = max_page;
+    let mut from = from_block;
+    let mut streak = 0;
+
+    while from <= to_block {
+        let to = to_block.min(from.saturating_add(page - 1));
+
+        match fetch(from, to).await {
+            Ok(page_logs) => {
+                logs.extend(page_logs);
+                from = to + 1;
+                streak += 1;
+
+                if streak == PAGE_GROWTH_STREAK {
+                    page = max_page.min(page.saturating_mul(2));
+                    streak = 0;
+                }
+            }
+            Err(err) if is_range_limit(&err) && page > 1 => {
+                page /= 2;
+                streak = 0;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(logs)
+}
+
+// fetches the logs matching the filter between the blocks, pages start at
+// `log_page_size` blocks and shrink when the node rejects their range
+pub async fn scan_logs(
+    client: &'static Client,
+    provider: &EvmProvider,
+    filter: &Value,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<Log>, RpcError> {
+    paginate(from_block, to_block, provider.log_page_size, |from, to| {
+        let mut filter = filter.clone();
+        filter["fromBlock"] = json!(format!("{from:#x}"));
+        filter["toBlock"] = json!(format!("{to:#x}"));
+
+        get_logs(client, provider, filter)
+    })
+    .await
+}
+
+fn transferred_ids(log: &Log) -> Result<Vec<U256>, RpcError> {
+    let uints = |typ: ParamType| [typ.clone(), typ];
+    let event = log.topics.first().map(|topic| topic.to_ascii_lowercase());
+    let data = from_hex(&log.data)?;
+
+    let ids = if event == Some(event_topic(TRANSFER_SINGLE)) {
+        decode(&uints(ParamType::Uint(256)), &data)?
+            .into_iter()
+            .next()
+            .and_then(Token::into_uint)
+            .into_iter()
+            .collect()
+    } else if event == Some(event_topic(TRANSFER_BATCH)) {
+        decode(
+            &uints(ParamType::Array(Box::new(ParamType::Uint(256)))),
+            &data,
+        )?
+        .into_iter()
+        .next()
+        .and_then(Token::into_array)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(Token::into_uint)
+        .collect()
+    } else {
+        vec![]
+    };
+
+    Ok(ids)
+}
+
+// the ids each account has ever received according to the transfer logs
+pub fn received_ids(logs: &[Log], accounts: &[Address]) -> Result<Vec<Vec<U256>>, RpcError> {
+    let mut received = HashMap::<Address, BTreeSet<U256>>::new();
+
+    for log in logs {
+        // the recipient is the third indexed parameter of both events
+        let Some(recipient) = log.topics.get(3) else {
+            continue;
+        };
+
+        received
+            .entry(topic_address(recipient)?)
+            .or_default()
+            .extend(transferred_ids(log)?);
+    }
+
+    let ids = accounts
+        .iter()
+        .map(|account| {
+            received
+                .get(account)
+                .map(|ids| ids.iter().copied().collect())
+                .unwrap_or_default()
+        })
+        .collect();
+
+    Ok(ids)
+}
+
+// scans the transfer logs of the collection up to the block
+pub async fn get_erc1155_received_ids(
+    client: &'static Client,
+    provider: &EvmProvider,
+    token_address: &str,
+    accounts: &[Address],
+    to_block: u64,
+) -> Result<Vec<Vec<U256>>, RpcError> {
+    let events = json!([event_topic(TRANSFER_SINGLE), event_topic(TRANSFER_BATCH)]);
+
+    let filters = accounts
+        .chunks(provider.chunk_size)
+        .map(|chunk| {
+            let recipients = chunk.iter().map(address_topic).collect::<Vec<_>>();
+
+            json!({
+                "address": token_address,
+                "topics": [events, null, null, recipients]
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let logs: Vec<Vec<Log>> = stream::iter(&filters)
+        .map(|filter| scan_logs(client, provider, filter, 0, to_block))
+        .buffered(provider.max_concurrency)
+        .try_collect()
+        .await?;
+
+    received_ids(&logs.concat(), accounts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        address_topic, event_topic, paginate, received_ids, topic_address, Log, TRANSFER_BATCH,
+        TRANSFER_SINGLE,
+    };
+    use crate::{
+        abi::{encode, parse_address, to_hex, Token},
+        balance::{JsonRpcError, RpcError},
+    };
+    use primitive_types::U256;
+    use std::sync::Mutex;
+
+    fn log(topics: Vec<String>, data: String, block: u64) -> Log {
+        Log {
+            topics,
+            data,
+            block_number: format!("{block:#x}"),
+            log_index: "0x0".to_string(),
+        }
+    }
+
+    #[test]
+    fn transfer_logs() {
+        assert_eq!(
+            event_topic(TRANSFER_SINGLE),
+            "0xc3d58168c5ae7397731d063d5bbf3d657854427343f4c083240f7aacaa2d0f62"
+        );
+        assert_eq!(
+            event_topic(TRANSFER_BATCH),
+            "0x4a39dc06d4c0dbc64b70af90fd698a233a518aa5d07e595d983b8c0526c8f7fb"
+        );
+
+        let user_1 = parse_address("0xe43878ce78934fe8007748ff481f03b8ee3b97de").unwrap();
+        let user_2 = parse_address("0x14ddfe8ea7ffc338015627d160ccaf99e8f16dd3").unwrap();
+        let user_3 = parse_address("0x283d678711daa088640c86a1ad3f12c00ec1252e").unwrap();
+        let operator = address_topic(&user_3);
+
+        let uint = |value: u64| Token::Uint(U256::from(value));
+        let uints = |values: &[u64]| Token::Array(values.iter().map(|v| uint(*v)).collect());
+
+        let logs = [
+            log(
+                vec![
+                    event_topic(TRANSFER_SINGLE),
+                    operator.clone(),
+                    operator.clone(),
+                    address_topic(&user_1),
+                ],
+                to_hex(&encode(&[uint(10868), uint(5)])),
+                1,
+            ),
+            log(
+                vec![
+                    event_topic(TRANSFER_BATCH),
+                    operator.clone(),
+                    operator.clone(),
+                    address_topic(&user_1),
+                ],
+                to_hex(&encode(&[uints(&[3, 10868, 1]), uints(&[1, 1, 1])])),
+                1,
+            ),
+            log(
+                vec![
+                    event_topic(TRANSFER_SINGLE),
+                    operator.clone(),
+                    address_topic(&user_1),
+                    address_topic(&user_2),
+                ],
+                to_hex(&encode(&[uint(42), uint(1)])),
+                1,
+            ),
+        ];
+
+        let ids = |values: &[u64]| values.iter().map(|v| U256::from(*v)).collect::<Vec<_>>();
+
+        assert_eq!(
+            received_ids(&logs, &[user_1, user_2, user_3]).unwrap(),
+            vec![ids(&[1, 3, 10868]), ids(&[42]), vec![]]
+        );
+
+        let truncated = Log {
+            data: "0x1234".to_string(),
+            ..logs[0].clone()
+        };
+        assert!(received_ids(&[truncated], &[user_1]).is_err());
+
+        assert_eq!(topic_address(&address_topic(&user_2)).unwrap(), user_2);
+        assert!(topic_address("0x1234").is_err());
+        assert_eq!(logs[2].position().unwrap(), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn log_pagination() {
+        let requests = Mutex::new(vec![]);
+
+        // the node rejects ranges wider than 3 blocks and returns a log per block
+        let logs = paginate(10, 20, 8, |from, to| {
+            requests.lock().unwrap().push((from, to));
+
+            async move {
+                if to - from >= 3 {
+                    return Err(RpcError::JsonRpc(JsonRpcError {
+                        code: -32005,
+                        message: "query returned more than 10000 results".to_string(),
+                    }));
+                }
+
+                Ok((from..=to)
+                    .map(|block| log(vec![], "0x".to_string(), block))
+                    .collect())
+            }
+        })
+        .await
+        .unwrap();
+
+        let blocks = logs
+            .iter()
+            .map(|log| log.position().unwrap().0)
+            .collect::<Vec<_>>();
+
+        assert_eq!(blocks, (10..=20).collect::<Vec<_>>());
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![
+                (10, 17),
+                (10, 13),
+                (10, 11),
+                (12, 13),
+                (14, 15),
+                (16, 17),
+                (18, 20)
+            ]
+        );
+
+        // other errors, like being rate limited with the same code, don't
+        // shrink the page
+        for (code, message) in [
+            (-32602, "invalid argument 0: hex string without 0x prefix"),
+            (-32005, "limit exceeded"),
+        ] {
+            requests.lock().unwrap().clear();
+
+            let failing = paginate(0, 10, 4, |from, to| {
+                requests.lock().unwrap().push((from, to));
+
+                async move {
+                    Err::<Vec<Log>, _>(RpcError::JsonRpc(JsonRpcError {
+                        code,
+                        message: message.to_string(),
+                    }))
+                }
+            });
+
+            assert!(failing.await.is_err());
+            assert_eq!(*requests.lock().unwrap(), vec![(0, 3)]);
+        }
+
+        assert!(paginate(5, 4, 4, |_, _| async { Ok(vec![]) })
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
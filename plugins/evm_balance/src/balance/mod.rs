@@ -1,31 +1,119 @@
-use crate::balance::contract::*;
-use guild_common::{Scalar, TokenType};
+use crate::{
+    abi::AbiError,
+    balance::{contract::*, logs::*},
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use guild_common::{BlockSpec, PluginError, Scalar, TokenIds, TokenType};
+use primitive_types::U256;
 use reqwest::Client;
 use serde::Deserialize;
-use serde_json::{json, Value};
+use std::{collections::HashSet, future::Future};
 use thiserror::Error;
 
+mod block;
 mod contract;
+mod logs;
+mod rpc;
+
+pub use block::{get_block, get_block_timestamps, parse_quantity, resolve_block, BlockId};
+pub use contract::{aggregate_calls, parse_addresses, Call, MulticallKind};
+pub use logs::{address_topic, event_topic, scan_logs, topic_address, Log};
+pub use rpc::{create_payload, JsonRpcError, RpcEndpoint, RpcResponse};
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct EvmProvider {
+    #[serde(default)]
     pub rpc_url: String,
+    #[serde(default)]
+    pub rpc_urls: Vec<RpcEndpoint>,
     pub contract: String,
+    #[serde(default)]
+    pub multicall: MulticallKind,
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
+    #[serde(default = "default_max_concurrency")]
+    pub max_concurrency: usize,
+    #[serde(default = "default_chunk_retries")]
+    pub chunk_retries: usize,
+    #[serde(default)]
+    pub block: BlockSpec,
+    #[serde(default = "default_log_page_size")]
+    pub log_page_size: u64,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default = "default_rpc_retries")]
+    pub rpc_retries: usize,
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+}
+
+fn default_chunk_size() -> usize {
+    500
+}
+
+fn default_max_concurrency() -> usize {
+    4
+}
+
+fn default_chunk_retries() -> usize {
+    2
+}
+
+fn default_log_page_size() -> u64 {
+    100_000
+}
+
+fn default_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_rpc_retries() -> usize {
+    2
+}
+
+fn default_backoff_ms() -> u64 {
+    250
 }
 
-#[derive(Deserialize)]
-pub struct RpcResponse {
-    pub result: String,
+fn default_batch_size() -> usize {
+    100
 }
 
 #[derive(Error, Debug)]
 pub enum RpcError {
     #[error(transparent)]
     Reqwest(#[from] reqwest::Error),
+    #[error(transparent)]
+    Abi(#[from] AbiError),
+    #[error(transparent)]
+    JsonRpc(#[from] JsonRpcError),
+    #[error(transparent)]
+    Decode(#[from] serde_json::Error),
+    #[error("Invalid JSON-RPC response: {0}")]
+    Response(String),
     #[error("{0}")]
     Other(String),
 }
 
+impl RpcError {
+    // whether another attempt, possibly at another endpoint, can succeed
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Reqwest(_) | Self::Response(_) => true,
+            Self::JsonRpc(error) => !error.is_revert(),
+            _ => false,
+        }
+    }
+}
+
+impl From<RpcError> for PluginError {
+    fn from(error: RpcError) -> Self {
+        PluginError::Rpc(error.to_string())
+    }
+}
+
 #[macro_export]
 macro_rules! rpc_error {
     ($code:expr) => {
@@ -33,67 +121,181 @@ macro_rules! rpc_error {
     };
 }
 
-const ETH_BALANCE_DIVIDER: Scalar = 10_u128.pow(18) as Scalar;
-
-fn create_payload(method: &str, params: Value, id: u32) -> Value {
-    json!({
-        "method"  : method,
-        "params"  : params,
-        "id"      : id,
-        "jsonrpc" : "2.0"
-    })
-}
+const ETH_DECIMALS: u32 = 18;
+// above this many account and id pairs the ids an account never received
+// aren't queried, which needs a scan of the transfer logs
+const MAX_ERC1155_PAIRS: usize = 10_000;
 
 impl EvmProvider {
+    // fetches the results of at most `chunk_size` items per call, runs
+    // at most `max_concurrency` calls at a time and retries chunks failing
+    // with a retryable error
+    pub async fn get_chunked<'a, T, R, F, Fut>(
+        &self,
+        items: &'a [T],
+        fetch: F,
+    ) -> Result<Vec<R>, RpcError>
+    where
+        F: Fn(&'a [T]) -> Fut,
+        Fut: Future<Output = Result<Vec<R>, RpcError>>,
+    {
+        let fetch = &fetch;
+
+        let chunks: Vec<Vec<R>> = stream::iter(items.chunks(self.chunk_size))
+            .map(|chunk| async move {
+                let mut retries = 0;
+
+                loop {
+                    match fetch(chunk).await {
+                        Ok(results) if results.len() == chunk.len() => return Ok(results),
+                        Ok(results) => {
+                            return Err(RpcError::Other(format!(
+                                "expected {} results, got {}",
+                                chunk.len(),
+                                results.len()
+                            )))
+                        }
+                        Err(err) if err.is_retryable() && retries < self.chunk_retries => {
+                            retries += 1;
+                            self.backoff(retries).await;
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+            })
+            .buffered(self.max_concurrency)
+            .try_collect()
+            .await?;
+
+        Ok(chunks.into_iter().flatten().collect())
+    }
+
+    // returns the values of each address, and the number of the block they
+    // were read at
     pub async fn get_balance_batch(
         &self,
         client: &'static Client,
         token_type: TokenType,
+        block: &BlockSpec,
         addresses: &[&str],
-    ) -> Result<Vec<Scalar>, RpcError> {
-        match token_type {
-            TokenType::Native => {
-                get_eth_balance_batch(client, &self.contract.to_string(), &self.rpc_url, addresses)
-                    .await
-            }
-            TokenType::Fungible { address } => {
-                get_erc20_balance_batch(
-                    client,
-                    &self.contract.to_string(),
-                    &self.rpc_url,
-                    &address,
-                    addresses,
-                )
+    ) -> Result<(Vec<Vec<Option<Scalar>>>, u64), PluginError> {
+        let (block, number) = resolve_block(client, self, block).await?;
+        let block = &block;
+
+        let balances = match token_type {
+            TokenType::Native => self
+                .get_chunked(addresses, |chunk| {
+                    get_eth_balance_batch(client, self, chunk, block)
+                })
                 .await
+                .map(per_address),
+            TokenType::Erc20 { address } => {
+                let decimals = get_erc20_decimals(client, self, address.as_str(), block).await?;
+
+                self.get_chunked(addresses, |chunk| {
+                    get_erc20_balance_batch(client, self, address.as_str(), decimals, chunk, block)
+                })
+                .await
+                .map(per_address)
             }
-            TokenType::NonFungible { address, id } => {
-                get_erc721_balance_batch(
-                    client,
-                    &self.contract.to_string(),
-                    &self.rpc_url,
-                    &address,
-                    &id,
-                    addresses,
-                )
+            // each user gets the number of matching ids they own
+            TokenType::Erc721 {
+                address,
+                id: Some(ids),
+            } => {
+                let ids = parse_token_ids(&ids)?;
+
+                let owners = self
+                    .get_chunked(&ids, |chunk| {
+                        get_erc721_owners(client, self, address.as_str(), chunk, block)
+                    })
+                    .await?;
+
+                count_owned(&owners, addresses).map(per_address)
+            }
+            TokenType::Erc721 { address, id: None } => self
+                .get_chunked(addresses, |chunk| {
+                    get_erc721_balance_batch(client, self, address.as_str(), chunk, block)
+                })
                 .await
+                .map(per_address),
+            TokenType::Erc1155 { address, id, mode } => {
+                let accounts = parse_addresses(addresses)?;
+                let requested = id.as_ref().map(parse_token_ids).transpose()?;
+
+                let ids_per_account = match &requested {
+                    Some(ids) if ids.len() * accounts.len() <= MAX_ERC1155_PAIRS => {
+                        vec![ids.clone(); accounts.len()]
+                    }
+                    // only the ids an account ever received can have a balance
+                    _ => {
+                        let received = get_erc1155_received_ids(
+                            client,
+                            self,
+                            address.as_str(),
+                            &accounts,
+                            number,
+                        )
+                        .await?;
+
+                        match &requested {
+                            Some(ids) => {
+                                let ids = ids.iter().collect::<HashSet<_>>();
+
+                                received
+                                    .into_iter()
+                                    .map(|received| {
+                                        received.into_iter().filter(|id| ids.contains(id)).collect()
+                                    })
+                                    .collect()
+                            }
+                            None => received,
+                        }
+                    }
+                };
+
+                let pairs = accounts
+                    .iter()
+                    .zip(&ids_per_account)
+                    .flat_map(|(account, ids)| ids.iter().map(|id| (*account, *id)))
+                    .collect::<Vec<_>>();
+
+                let balances = self
+                    .get_chunked(&pairs, |chunk| {
+                        get_erc1155_balance_batch(client, self, address.as_str(), chunk, block)
+                    })
+                    .await?;
+
+                Ok(group_erc1155_balances(
+                    &balances,
+                    &ids_per_account,
+                    requested.as_deref(),
+                    mode,
+                ))
             }
-            TokenType::Special { address, id } => match id {
-                Some(token_id) => {
-                    get_erc1155_balance_batch(
-                        client,
-                        &self.rpc_url,
-                        address.clone(),
-                        &token_id,
-                        addresses,
-                    )
-                    .await
-                }
-                None => Ok(vec![0.0; addresses.len()]),
-            },
-        }
+            token_type => {
+                return Err(PluginError::invalid(
+                    "metadata",
+                    format!("{} tokens are not supported", token_type.standard()),
+                ))
+            }
+        };
+
+        Ok((balances?, number))
     }
 }
 
+fn per_address(balances: Vec<Option<Scalar>>) -> Vec<Vec<Option<Scalar>>> {
+    balances.into_iter().map(|balance| vec![balance]).collect()
+}
+
+fn parse_token_ids(ids: &TokenIds) -> Result<Vec<U256>, RpcError> {
+    ids.to_vec()
+        .iter()
+        .map(|id| rpc_error!(U256::from_dec_str(id.as_str())))
+        .collect()
+}
+
 #[cfg(test)]
 mod common {
     pub const RPC_URL: &str = "https://eth.public-rpc.com";
@@ -110,44 +312,152 @@ mod common {
 
 #[cfg(test)]
 mod test {
-    use crate::balance::{common::*, EvmProvider};
-    use guild_common::TokenType::*;
-    use primitive_types::U256;
+    use crate::balance::{common::*, EvmProvider, MulticallKind, RpcError};
+    use guild_common::{
+        BalanceMode, BlockSpec, ContractAddress, Scalar, TokenId, TokenIds, TokenType::*,
+    };
     use reqwest::Client;
+    use std::{collections::HashMap, str::FromStr, sync::Mutex};
 
     fn provider() -> EvmProvider {
         EvmProvider {
             rpc_url: RPC_URL.to_string(),
-            contract: "0x5BA1e12693Dc8F9c48aAD8770482f4739bEeD696".to_string(),
+            rpc_urls: vec![],
+            contract: "0xcA11bde05977b3631167028862bE2a173976CA11".to_string(),
+            multicall: MulticallKind::Aggregate3,
+            chunk_size: 1,
+            max_concurrency: 2,
+            chunk_retries: 1,
+            block: BlockSpec::Latest,
+            log_page_size: 1_000_000,
+            timeout_ms: 10_000,
+            rpc_retries: 1,
+            backoff_ms: 100,
+            batch_size: 2,
         }
     }
 
+    #[tokio::test]
+    async fn chunked_balances() {
+        let addresses = ["0", "1", "2", "3", "4", "5", "6", "7"];
+        let attempts = Mutex::new(HashMap::<String, usize>::new());
+
+        let provider = EvmProvider {
+            chunk_size: 3,
+            ..provider()
+        };
+
+        // the chunk starting at "3" fails once, the others succeed at first
+        let fetch = |chunk: &[&str]| {
+            let first = chunk[0].to_string();
+            let attempt = {
+                let mut attempts = attempts.lock().unwrap();
+                let attempt = attempts.entry(first.clone()).or_default();
+                *attempt += 1;
+                *attempt
+            };
+            let balances = chunk
+                .iter()
+                .map(|address| Some(Scalar::from_str(address).unwrap()))
+                .collect::<Vec<_>>();
+
+            async move {
+                if first == "3" && attempt == 1 {
+                    Err(RpcError::Response("empty body".to_string()))
+                } else {
+                    Ok(balances)
+                }
+            }
+        };
+
+        let balances = provider.get_chunked(&addresses, fetch).await.unwrap();
+
+        assert_eq!(
+            balances,
+            addresses
+                .iter()
+                .map(|address| Some(Scalar::from_str(address).unwrap()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            *attempts.lock().unwrap(),
+            HashMap::from([
+                ("0".to_string(), 1),
+                ("3".to_string(), 2),
+                ("6".to_string(), 1)
+            ])
+        );
+
+        let provider = EvmProvider {
+            chunk_retries: 0,
+            ..provider
+        };
+        attempts.lock().unwrap().clear();
+
+        assert!(provider.get_chunked(&addresses, fetch).await.is_err());
+
+        // errors that fail the same way every time aren't retried
+        let provider = EvmProvider {
+            chunk_retries: 2,
+            ..provider
+        };
+        attempts.lock().unwrap().clear();
+
+        let failing = |chunk: &[&str]| {
+            *attempts
+                .lock()
+                .unwrap()
+                .entry(chunk[0].to_string())
+                .or_default() += 1;
+
+            async { Err::<Vec<Option<Scalar>>, _>(RpcError::Other("reverted".to_string())) }
+        };
+
+        assert!(provider.get_chunked(&addresses, failing).await.is_err());
+        assert_eq!(attempts.lock().unwrap()["0"], 1);
+    }
+
     #[tokio::test]
     async fn rpc_get_coin_balance_batch() {
         let client: &'static Client = Box::leak(Box::new(Client::new()));
 
         assert_eq!(
             provider()
-                .get_balance_batch(client, Native, &[USER_1_ADDR, USER_2_ADDR])
+                .get_balance_batch(
+                    client,
+                    Native,
+                    &BlockSpec::Latest,
+                    &[USER_1_ADDR, USER_2_ADDR]
+                )
                 .await
-                .unwrap(),
-            vec![0.000464468855704627, 0.3919455024496939]
+                .unwrap()
+                .0,
+            vec![
+                vec![Some(Scalar::from_str("0.000464468855704627").unwrap())],
+                vec![Some(Scalar::from_str("0.3919455024496939").unwrap())]
+            ]
         );
     }
 
     #[tokio::test]
     async fn rpc_get_erc20_balance_batch() {
-        let token_type = Fungible {
-            address: ERC20_ADDR.to_string(),
+        let token_type = Erc20 {
+            address: ContractAddress::new(ERC20_ADDR).unwrap(),
         };
         let client: &'static Client = Box::leak(Box::new(Client::new()));
 
         assert_eq!(
             provider()
-                .get_balance_batch(client, token_type, &[USER_1_ADDR, USER_2_ADDR])
+                .get_balance_batch(
+                    client,
+                    token_type,
+                    &BlockSpec::Latest,
+                    &[USER_1_ADDR, USER_2_ADDR]
+                )
                 .await
-                .unwrap(),
-            vec![0.0, 100.0]
+                .unwrap()
+                .0,
+            vec![vec![Some(Scalar::from(0))], vec![Some(Scalar::from(100))]]
         );
     }
 
@@ -155,28 +465,64 @@ mod test {
     async fn rpc_get_erc721_balance_batch() {
         let client: &'static Client = Box::leak(Box::new(Client::new()));
 
-        let token_type_without_id = NonFungible {
-            address: ERC721_ADDR.to_string(),
+        let token_type_without_id = Erc721 {
+            address: ContractAddress::new(ERC721_ADDR).unwrap(),
             id: None,
         };
-        let token_type_with_id = NonFungible {
-            address: ERC721_ADDR.to_string(),
-            id: Some(ERC721_ID.to_string()),
+        let token_type_with_id = Erc721 {
+            address: ContractAddress::new(ERC721_ADDR).unwrap(),
+            id: Some(TokenId::new(ERC721_ID).unwrap().into()),
+        };
+        // the second id doesn't exist, so its ownerOf call reverts
+        let token_type_with_ids = Erc721 {
+            address: ContractAddress::new(ERC721_ADDR).unwrap(),
+            id: Some(
+                TokenIds::list(vec![
+                    TokenId::new(ERC721_ID).unwrap(),
+                    TokenId::new("1").unwrap(),
+                ])
+                .unwrap(),
+            ),
         };
 
         assert_eq!(
             provider()
-                .get_balance_batch(client, token_type_without_id, &[USER_1_ADDR, USER_2_ADDR])
+                .get_balance_batch(
+                    client,
+                    token_type_without_id,
+                    &BlockSpec::Latest,
+                    &[USER_1_ADDR, USER_2_ADDR]
+                )
                 .await
-                .unwrap(),
-            vec![1.0, 1.0]
+                .unwrap()
+                .0,
+            vec![vec![Some(Scalar::from(1))], vec![Some(Scalar::from(1))]]
         );
         assert_eq!(
             provider()
-                .get_balance_batch(client, token_type_with_id, &[USER_1_ADDR, USER_2_ADDR])
+                .get_balance_batch(
+                    client,
+                    token_type_with_id,
+                    &BlockSpec::Latest,
+                    &[USER_1_ADDR, USER_2_ADDR]
+                )
                 .await
-                .unwrap(),
-            vec![1.0, 0.0]
+                .unwrap()
+                .0,
+            vec![vec![Some(Scalar::from(1))], vec![Some(Scalar::from(0))]]
+        );
+        assert_eq!(
+            provider()
+                .get_balance_batch(
+                    client,
+                    token_type_with_ids,
+                    &BlockSpec::Latest,
+                    &[USER_1_ADDR, USER_2_ADDR]
+                )
+                .await
+                .unwrap()
+                .0,
+            vec![vec![Some(Scalar::from(1))], vec![Some(Scalar::from(0))]]
         );
     }
 
@@ -184,17 +530,24 @@ mod test {
     async fn rpc_get_erc1155_balance_batch() {
         let client: &'static Client = Box::leak(Box::new(Client::new()));
 
-        let token_type_with_id = Special {
-            address: ERC1155_ADDR.to_string(),
-            id: Some(U256::from(ERC1155_ID).to_string()),
+        let token_type_with_id = Erc1155 {
+            address: ContractAddress::new(ERC1155_ADDR).unwrap(),
+            id: Some(TokenId::new(&ERC1155_ID.to_string()).unwrap().into()),
+            mode: BalanceMode::Sum,
         };
 
         assert_eq!(
             provider()
-                .get_balance_batch(client, token_type_with_id, &[USER_1_ADDR, USER_3_ADDR])
+                .get_balance_batch(
+                    client,
+                    token_type_with_id,
+                    &BlockSpec::Latest,
+                    &[USER_1_ADDR, USER_3_ADDR]
+                )
                 .await
-                .unwrap(),
-            vec![0.0, 15.0]
+                .unwrap()
+                .0,
+            vec![vec![Some(Scalar::from(0))], vec![Some(Scalar::from(15))]]
         );
     }
 }
@@ -0,0 +1,529 @@
+use crate::balance::{EvmProvider, RpcError};
+use futures::{stream, StreamExt, TryStreamExt};
+use reqwest::Client;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock, PoisonError,
+    },
+    time::Duration,
+};
+use thiserror::Error;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Hash)]
+pub struct RpcEndpoint {
+    pub url: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+#[derive(Error, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[error("JSON-RPC error {code}: {message}")]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl JsonRpcError {
+    // a reverted call fails the same way on every node
+    pub fn is_revert(&self) -> bool {
+        self.code == 3 || self.message.contains("revert")
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RpcResponse {
+    #[serde(default)]
+    pub id: Value,
+    #[serde(default)]
+    pub result: Value,
+    pub error: Option<JsonRpcError>,
+}
+
+impl RpcResponse {
+    pub fn parse<T: DeserializeOwned>(self) -> Result<T, RpcError> {
+        match self.error {
+            Some(error) => Err(RpcError::JsonRpc(error)),
+            None => Ok(serde_json::from_value(self.result)?),
+        }
+    }
+}
+
+pub fn create_payload(method: &str, params: Value, id: u32) -> Value {
+    json!({
+        "method"  : method,
+        "params"  : params,
+        "id"      : id,
+        "jsonrpc" : "2.0"
+    })
+}
+
+fn parse_response<T: DeserializeOwned>(body: Value) -> Result<T, RpcError> {
+    serde_json::from_value::<RpcResponse>(body)
+        .map_err(|err| RpcError::Response(err.to_string()))?
+        .parse()
+}
+
+// matches the responses of a batch to the requests by id, an error that
+// another endpoint might not return fails the whole batch
+fn parse_batch<T: DeserializeOwned>(
+    body: Value,
+    len: usize,
+) -> Result<Vec<Result<T, RpcError>>, RpcError> {
+    let responses = match body {
+        Value::Array(responses) => responses,
+        // nodes without batch support answer with a single error
+        body => {
+            return Err(parse_response::<Value>(body).err().unwrap_or_else(|| {
+                RpcError::Response("expected an array of responses".to_string())
+            }))
+        }
+    };
+
+    let mut results: Vec<Option<Result<T, RpcError>>> = (0..len).map(|_| None).collect();
+
+    for response in responses {
+        let response: RpcResponse =
+            serde_json::from_value(response).map_err(|err| RpcError::Response(err.to_string()))?;
+        let slot = response
+            .id
+            .as_u64()
+            .and_then(|id| results.get_mut(id as usize))
+            .ok_or_else(|| RpcError::Response(format!("unexpected response id {}", response.id)))?;
+
+        match response.parse() {
+            Err(err) if err.is_retryable() => return Err(err),
+            result => *slot = Some(result),
+        }
+    }
+
+    results
+        .into_iter()
+        .enumerate()
+        .map(|(id, result)| {
+            result.ok_or_else(|| RpcError::Response(format!("missing response {id}")))
+        })
+        .collect()
+}
+
+// the providers are deserialized for every evaluation, so the number of
+// requests sent to each set of endpoints is kept for as long as the plugin is
+// loaded to spread them by weight across evaluations
+fn request_counter(endpoints: &[RpcEndpoint]) -> Arc<AtomicUsize> {
+    static COUNTERS: OnceLock<Mutex<HashMap<Vec<RpcEndpoint>, Arc<AtomicUsize>>>> = OnceLock::new();
+
+    COUNTERS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(endpoints.to_vec())
+        .or_default()
+        .clone()
+}
+
+// the endpoints in the order a request tries them: the first one is picked
+// by weighted round robin, the others follow by weight
+fn failover_order(endpoints: &[RpcEndpoint], request: usize) -> Vec<usize> {
+    let total = endpoints
+        .iter()
+        .map(|endpoint| u64::from(endpoint.weight))
+        .sum::<u64>()
+        .max(1);
+    let mut slot = request as u64 % total;
+
+    let first = endpoints
+        .iter()
+        .position(|endpoint| {
+            let weight = u64::from(endpoint.weight);
+
+            if slot < weight {
+                true
+            } else {
+                slot -= weight;
+                false
+            }
+        })
+        .unwrap_or_default();
+
+    let mut order = (0..endpoints.len())
+        .filter(|index| *index != first)
+        .collect::<Vec<_>>();
+    order.sort_by_key(|index| Reverse(endpoints[*index].weight));
+    order.insert(0, first);
+
+    order
+}
+
+impl EvmProvider {
+    // rpc_url followed by rpc_urls
+    pub fn endpoints(&self) -> Vec<RpcEndpoint> {
+        let single = (!self.rpc_url.is_empty()).then(|| RpcEndpoint {
+            url: self.rpc_url.clone(),
+            weight: default_weight(),
+        });
+
+        single.into_iter().chain(self.rpc_urls.clone()).collect()
+    }
+
+    // waits `backoff_ms`, doubled on every further retry
+    pub(crate) async fn backoff(&self, retry: usize) {
+        let backoff = self
+            .backoff_ms
+            .saturating_mul(1u64 << retry.saturating_sub(1).min(16));
+
+        tokio::time::sleep(Duration::from_millis(backoff)).await;
+    }
+
+    // posts the payload to the endpoints until one of them answers, and
+    // starts over with exponential backoff when all of them fail, errors that
+    // aren't `retryable` are returned right away
+    async fn send<R, F>(
+        &self,
+        client: &Client,
+        payload: &Value,
+        parse: F,
+        retryable: impl Fn(&RpcError) -> bool,
+    ) -> Result<R, RpcError>
+    where
+        F: Fn(Value) -> Result<R, RpcError>,
+    {
+        let endpoints = self.endpoints();
+        let request = request_counter(&endpoints).fetch_add(1, Ordering::Relaxed);
+        let mut last_error = RpcError::Other("no RPC endpoints configured".to_string());
+
+        for round in 0..=self.rpc_retries {
+            if round > 0 {
+                self.backoff(round).await;
+            }
+
+            for index in failover_order(&endpoints, request) {
+                let body = async {
+                    client
+                        .post(&endpoints[index].url)
+                        .timeout(Duration::from_millis(self.timeout_ms))
+                        .json(payload)
+                        .send()
+                        .await?
+                        .error_for_status()?
+                        .json::<Value>()
+                        .await
+                };
+
+                match body.await.map_err(RpcError::from).and_then(&parse) {
+                    Err(err) if retryable(&err) => last_error = err,
+                    res => return res,
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    pub async fn request<T: DeserializeOwned>(
+        &self,
+        client: &Client,
+        method: &str,
+        params: Value,
+    ) -> Result<T, RpcError> {
+        self.request_with(client, method, params, RpcError::is_retryable)
+            .await
+    }
+
+    // like `request`, with the errors worth another attempt decided by the
+    // caller
+    pub(crate) async fn request_with<T: DeserializeOwned>(
+        &self,
+        client: &Client,
+        method: &str,
+        params: Value,
+        retryable: impl Fn(&RpcError) -> bool,
+    ) -> Result<T, RpcError> {
+        let payload = create_payload(method, params, 0);
+
+        self.send(client, &payload, parse_response, retryable).await
+    }
+
+    // sends the requests in JSON-RPC batches of at most `batch_size`, a
+    // reverted call only fails its own result
+    pub async fn batch_request<T: DeserializeOwned>(
+        &self,
+        client: &Client,
+        requests: &[(&str, Value)],
+    ) -> Result<Vec<Result<T, RpcError>>, RpcError> {
+        let batches: Vec<Vec<Result<T, RpcError>>> = stream::iter(requests.chunks(self.batch_size))
+            .map(|batch| async move {
+                let payload = batch
+                    .iter()
+                    .enumerate()
+                    .map(|(id, (method, params))| create_payload(method, params.clone(), id as u32))
+                    .collect::<Value>();
+
+                self.send(
+                    client,
+                    &payload,
+                    |body| parse_batch(body, batch.len()),
+                    RpcError::is_retryable,
+                )
+                .await
+            })
+            .buffered(self.max_concurrency)
+            .try_collect()
+            .await?;
+
+        Ok(batches.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        failover_order, parse_batch, parse_response, request_counter, JsonRpcError, RpcEndpoint,
+    };
+    use crate::balance::{EvmProvider, RpcError};
+    use reqwest::Client;
+    use serde_json::{json, Value};
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    // serves the response `respond` gives to each JSON-RPC payload, returns
+    // the url and the number of requests served
+    fn mock_endpoint(respond: fn(&Value) -> Value) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let served = Arc::new(AtomicUsize::new(0));
+        let counter = served.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut length = 0;
+
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                let response = respond(&serde_json::from_slice(&body).unwrap()).to_string();
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{response}",
+                    response.len()
+                )
+                .unwrap();
+            }
+        });
+
+        (url, served)
+    }
+
+    #[test]
+    fn rpc_responses() {
+        let result: String =
+            parse_response(json!({"jsonrpc": "2.0", "id": 0, "result": "0x01"})).unwrap();
+        assert_eq!(result, "0x01");
+
+        let missing: Option<String> =
+            parse_response(json!({"jsonrpc": "2.0", "id": 0, "result": null})).unwrap();
+        assert_eq!(missing, None);
+
+        let revert = parse_response::<String>(json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "error": { "code": 3, "message": "execution reverted", "data": "0x" }
+        }))
+        .unwrap_err();
+        assert!(matches!(revert, RpcError::JsonRpc(ref err) if err.is_revert()));
+        assert!(!revert.is_retryable());
+
+        let limited = parse_response::<String>(json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "error": { "code": -32005, "message": "limit exceeded" }
+        }))
+        .unwrap_err();
+        assert_eq!(limited.to_string(), "JSON-RPC error -32005: limit exceeded");
+        assert!(limited.is_retryable());
+
+        let internal = parse_response::<String>(json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "error": { "code": -32603, "message": "internal error" }
+        }))
+        .unwrap_err();
+        assert!(internal.is_retryable());
+
+        assert!(parse_response::<String>(json!("<html>"))
+            .unwrap_err()
+            .is_retryable());
+        assert!(!parse_response::<u64>(json!({"id": 0, "result": "0x01"}))
+            .unwrap_err()
+            .is_retryable());
+    }
+
+    #[test]
+    fn rpc_batches() {
+        let reverted = JsonRpcError {
+            code: -32000,
+            message: "execution reverted".to_string(),
+        };
+
+        let results = parse_batch::<String>(
+            json!([
+                { "id": 2, "result": "0x02" },
+                { "id": 0, "result": "0x00" },
+                { "id": 1, "error": { "code": -32000, "message": "execution reverted" } }
+            ]),
+            3,
+        )
+        .unwrap();
+
+        assert_eq!(results[0].as_ref().unwrap(), "0x00");
+        assert!(matches!(&results[1], Err(RpcError::JsonRpc(err)) if err == &reverted));
+        assert_eq!(results[2].as_ref().unwrap(), "0x02");
+
+        let failures = [
+            json!([{ "id": 0, "result": "0x00" }]),
+            json!([{ "id": 0, "result": "0x00" }, { "id": 5, "result": "0x05" }]),
+            json!([
+                { "id": 0, "result": "0x00" },
+                { "id": 1, "error": { "code": -32603, "message": "internal error" } }
+            ]),
+            json!({ "id": null, "error": { "code": -32600, "message": "batch not supported" } }),
+        ];
+
+        for body in failures {
+            assert!(parse_batch::<String>(body, 2).unwrap_err().is_retryable());
+        }
+    }
+
+    #[test]
+    fn endpoint_failover() {
+        let endpoint = |url: &str, weight| RpcEndpoint {
+            url: url.to_string(),
+            weight,
+        };
+        let endpoints = [endpoint("a", 1), endpoint("b", 3), endpoint("c", 2)];
+
+        let firsts = (0..12)
+            .map(|request| failover_order(&endpoints, request)[0])
+            .collect::<Vec<_>>();
+
+        assert_eq!(firsts, [0, 1, 1, 1, 2, 2, 0, 1, 1, 1, 2, 2]);
+        assert_eq!(failover_order(&endpoints, 0), [0, 1, 2]);
+        assert_eq!(failover_order(&endpoints, 4), [2, 1, 0]);
+
+        let provider: EvmProvider = serde_json::from_value(json!({
+            "rpc_url": "https://eth.public-rpc.com",
+            "rpc_urls": [
+                { "url": "https://rpc.ankr.com/eth", "weight": 3 },
+                { "url": "https://cloudflare-eth.com" }
+            ],
+            "contract": "0xcA11bde05977b3631167028862bE2a173976CA11"
+        }))
+        .unwrap();
+
+        assert_eq!(
+            provider.endpoints(),
+            [
+                endpoint("https://eth.public-rpc.com", 1),
+                endpoint("https://rpc.ankr.com/eth", 3),
+                endpoint("https://cloudflare-eth.com", 1)
+            ]
+        );
+
+        // the rotation carries over to providers with the same endpoints
+        let counted = [endpoint("counted-a", 1), endpoint("counted-b", 2)];
+
+        assert_eq!(request_counter(&counted).fetch_add(1, Ordering::Relaxed), 0);
+        assert_eq!(
+            request_counter(&counted.clone()).fetch_add(1, Ordering::Relaxed),
+            1
+        );
+        assert_eq!(
+            request_counter(&counted[..1]).fetch_add(1, Ordering::Relaxed),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn rate_limit_failover() {
+        let rate_limited = |_: &Value| json!({ "jsonrpc": "2.0", "id": 0, "error": { "code": -32005, "message": "limit exceeded" } });
+        let answering = |payload: &Value| match payload {
+            Value::Array(requests) => requests
+                .iter()
+                .map(|request| json!({ "jsonrpc": "2.0", "id": request["id"], "result": "0x01" }))
+                .collect(),
+            request => json!({ "jsonrpc": "2.0", "id": request["id"], "result": "0x01" }),
+        };
+
+        let (limited_url, limited) = mock_endpoint(rate_limited);
+        let (answering_url, answered) = mock_endpoint(answering);
+
+        // the rate limited endpoint is weighted to be tried first
+        let provider: EvmProvider = serde_json::from_value(json!({
+            "rpc_urls": [
+                { "url": limited_url, "weight": 10 },
+                { "url": answering_url }
+            ],
+            "contract": "0xcA11bde05977b3631167028862bE2a173976CA11",
+            "rpc_retries": 0,
+            "backoff_ms": 0
+        }))
+        .unwrap();
+        let client = Client::new();
+
+        let result: String = provider
+            .request(&client, "eth_blockNumber", json!([]))
+            .await
+            .unwrap();
+
+        assert_eq!(result, "0x01");
+        assert_eq!(limited.load(Ordering::SeqCst), 1);
+        assert_eq!(answered.load(Ordering::SeqCst), 1);
+
+        let results = provider
+            .batch_request::<String>(
+                &client,
+                &[("eth_blockNumber", json!([])), ("eth_chainId", json!([]))],
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            results.into_iter().collect::<Result<Vec<_>, _>>().unwrap(),
+            ["0x01", "0x01"]
+        );
+        assert_eq!(limited.load(Ordering::SeqCst), 2);
+        assert_eq!(answered.load(Ordering::SeqCst), 2);
+    }
+}
@@ -3,62 +3,71 @@
 #![allow(clippy::multiple_crate_versions)]
 #![deny(unused_crate_dependencies)]
 
-mod balance;
+pub mod abi;
+pub mod balance;
+pub mod users;
+mod validation;
 
 use balance::EvmProvider;
-use guild_common::{Scalar, TokenType, User};
+use guild_common::{BlockSpec, EvmBalanceMetadata, PluginError, PluginOutput, User};
 use reqwest::Client;
 use tokio::runtime::Runtime;
+use users::{user_addresses, values_per_user};
+pub use validation::validate_provider;
+use validation::{invalid_metadata, validate_token_type};
 
-#[no_mangle]
+fn parse_input(
+    metadata: &str,
+    secrets: &str,
+) -> Result<(EvmProvider, EvmBalanceMetadata, BlockSpec), PluginError> {
+    let provider: EvmProvider =
+        serde_json::from_str(secrets).map_err(|err| PluginError::Config(err.to_string()))?;
+    let metadata: EvmBalanceMetadata = metadata
+        .parse()
+        .map_err(|err| invalid_metadata(metadata, err))?;
+
+    validate_provider(&provider)?;
+    validate_token_type(&metadata.token)?;
+
+    // a block in the metadata takes precedence over the provider's
+    let block = metadata
+        .block
+        .clone()
+        .unwrap_or_else(|| provider.block.clone());
+
+    Ok((provider, metadata, block))
+}
+
+#[cfg_attr(feature = "plugin", no_mangle)]
+pub fn validate(metadata: &str, secrets: &str) -> Result<(), PluginError> {
+    parse_input(metadata, secrets).map(|_| ())
+}
+
+#[cfg_attr(feature = "plugin", no_mangle)]
 pub fn retrieve(
     client: &'static Client,
     users: &[User],
     metadata: &str,
     secrets: &str,
-) -> Result<Vec<Vec<Scalar>>, Box<dyn std::error::Error>> {
-    let provider: EvmProvider = serde_json::from_str(secrets)?;
-    let token_type: TokenType = serde_json::from_str(metadata)?;
+) -> Result<PluginOutput, PluginError> {
+    let (provider, metadata, block) = parse_input(metadata, secrets)?;
 
-    let addresses_with_ids: Vec<(u64, &str)> = users
-        .iter()
-        .flat_map(|user| {
-            user.identities("evm_address")
-                .map(|identities| {
-                    identities
-                        .iter()
-                        .map(|address| (user.id, address.as_ref()))
-                        .collect::<Vec<(u64, &str)>>()
-                })
-                .unwrap_or_default()
-        })
-        .collect();
+    let addresses_with_ids = user_addresses(users);
 
     let addresses: Vec<&str> = addresses_with_ids
         .iter()
         .map(|(_, address)| *address)
         .collect();
 
-    let rt = Runtime::new()?;
+    let rt = Runtime::new().map_err(|err| PluginError::Other(err.to_string()))?;
 
-    let balances: Vec<_> =
-        rt.block_on(provider.get_balance_batch(client, token_type, &addresses))?;
+    let (balances, block) =
+        rt.block_on(provider.get_balance_batch(client, metadata.token, &block, &addresses))?;
 
-    let id_balances = addresses_with_ids
-        .iter()
-        .zip(balances.iter())
-        .map(|((user_id, _), balance)| (*user_id, *balance))
-        .collect::<Vec<(u64, Scalar)>>();
-
-    let res = users
-        .iter()
-        .map(|user| {
-            id_balances
-                .iter()
-                .filter_map(|(i, balance)| if &user.id == i { Some(*balance) } else { None })
-                .collect()
-        })
-        .collect();
+    let res = values_per_user(users, &addresses_with_ids, balances);
 
-    Ok(res)
+    Ok(PluginOutput {
+        values: res,
+        block: Some(block),
+    })
 }
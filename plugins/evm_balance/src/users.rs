@@ -0,0 +1,77 @@
+use guild_common::{Scalar, User};
+use std::collections::HashMap;
+
+// the evm addresses of the users, each with the id of the user it belongs to
+pub fn user_addresses(users: &[User]) -> Vec<(u64, &str)> {
+    users
+        .iter()
+        .flat_map(|user| {
+            user.identities("evm_address")
+                .into_iter()
+                .flatten()
+                .map(|address| (user.id, address.as_str()))
+        })
+        .collect()
+}
+
+// collects the values of each address for the user it belongs to
+pub fn values_per_user(
+    users: &[User],
+    addresses: &[(u64, &str)],
+    values: impl IntoIterator<Item = Vec<Option<Scalar>>>,
+) -> Vec<Vec<Option<Scalar>>> {
+    let mut user_values = HashMap::<u64, Vec<Option<Scalar>>>::new();
+
+    for ((user_id, _), values) in addresses.iter().zip(values) {
+        user_values.entry(*user_id).or_default().extend(values);
+    }
+
+    users
+        .iter()
+        .map(|user| user_values.get(&user.id).cloned().unwrap_or_default())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{user_addresses, values_per_user};
+    use guild_common::{Scalar, User};
+
+    #[test]
+    fn user_values() {
+        let users: Vec<User> = serde_json::from_str(
+            r#"[
+                { "id": 1, "identities": { "evm_address": ["0xe43878ce78934fe8007748ff481f03b8ee3b97de", "0x14ddfe8ea7ffc338015627d160ccaf99e8f16dd3"] } },
+                { "id": 2, "identities": {} },
+                { "id": 3, "identities": { "evm_address": ["0x283d678711daa088640c86a1ad3f12c00ec1252e"] } }
+            ]"#,
+        )
+        .unwrap();
+
+        let addresses = user_addresses(&users);
+
+        assert_eq!(
+            addresses,
+            [
+                (1, "0xe43878ce78934fe8007748ff481f03b8ee3b97de"),
+                (1, "0x14ddfe8ea7ffc338015627d160ccaf99e8f16dd3"),
+                (3, "0x283d678711daa088640c86a1ad3f12c00ec1252e")
+            ]
+        );
+
+        let values = vec![
+            vec![Some(Scalar::from(1))],
+            vec![None, Some(Scalar::from(2))],
+            vec![Some(Scalar::from(3))],
+        ];
+
+        assert_eq!(
+            values_per_user(&users, &addresses, values),
+            vec![
+                vec![Some(Scalar::from(1)), None, Some(Scalar::from(2))],
+                vec![],
+                vec![Some(Scalar::from(3))]
+            ]
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use crate::balance::EvmProvider;
+use guild_common::{
+    ContractAddress, FieldError, PluginError, RequirementType, TokenIds, TokenType,
+};
+use serde_json::Value;
+
+// serde stops at the first invalid field, so the token fields are checked one
+// by one to report each of them under its own name
+fn token_field_errors(metadata: &str) -> Vec<FieldError> {
+    let Ok(Value::Object(metadata)) = serde_json::from_str(metadata) else {
+        return vec![];
+    };
+
+    metadata
+        .values()
+        .filter_map(Value::as_object)
+        .flatten()
+        .filter_map(|(field, value)| {
+            let error = match field.as_str() {
+                "address" => serde_json::from_value::<ContractAddress>(value.clone()).err(),
+                "id" => serde_json::from_value::<Option<TokenIds>>(value.clone()).err(),
+                _ => None,
+            };
+
+            error.map(|error| FieldError::new(field, error))
+        })
+        .collect()
+}
+
+pub fn invalid_metadata(metadata: &str, error: impl ToString) -> PluginError {
+    let errors = token_field_errors(metadata);
+
+    if errors.is_empty() {
+        PluginError::invalid("metadata", error)
+    } else {
+        PluginError::InvalidInput(errors)
+    }
+}
+
+pub fn validate_provider(provider: &EvmProvider) -> Result<(), PluginError> {
+    ContractAddress::new(&provider.contract)
+        .map_err(|err| PluginError::Config(FieldError::new("contract", err).to_string()))?;
+
+    if provider.chunk_size == 0 || provider.max_concurrency == 0 {
+        return Err(PluginError::Config(
+            "chunk_size and max_concurrency must be at least 1".to_string(),
+        ));
+    }
+
+    if provider.log_page_size == 0 {
+        return Err(PluginError::Config(
+            "log_page_size must be at least 1".to_string(),
+        ));
+    }
+
+    let endpoints = provider.endpoints();
+
+    if endpoints.is_empty() {
+        return Err(PluginError::Config(
+            "rpc_url or rpc_urls must be set".to_string(),
+        ));
+    }
+
+    if let Some(endpoint) = endpoints.iter().find(|endpoint| endpoint.weight == 0) {
+        return Err(PluginError::Config(format!(
+            "the weight of {} must be at least 1",
+            endpoint.url
+        )));
+    }
+
+    if provider.batch_size == 0 {
+        return Err(PluginError::Config(
+            "batch_size must be at least 1".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn validate_token_type(token_type: &TokenType) -> Result<(), PluginError> {
+    token_type
+        .check_supported(RequirementType::EvmBalance)
+        .map_err(|err| PluginError::invalid("metadata", err))
+}
+
+#[cfg(test)]
+mod test {
+    use super::validate_token_type;
+    use crate::validate;
+    use guild_common::{ContractAddress, FieldError, PluginError, TokenType};
+
+    const SECRETS: &str = r#"{
+        "rpc_url": "https://eth.public-rpc.com",
+        "contract": "0x5BA1e12693Dc8F9c48aAD8770482f4739bEeD696"
+    }"#;
+
+    fn field_errors(metadata: &str) -> Vec<FieldError> {
+        match validate(metadata, SECRETS) {
+            Err(PluginError::InvalidInput(errors)) => errors,
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn token_type_validation() {
+        assert!(validate_token_type(&TokenType::Native).is_ok());
+        assert!(validate_token_type(&TokenType::Erc20 {
+            address: ContractAddress::new("0x458691c1692cd82facfb2c5127e36d63213448a8").unwrap(),
+        })
+        .is_ok());
+
+        let errors =
+            field_errors(r#"{"Erc20":{"address":"0x458691c1692cd82facfb2c5127e36d63213448"}}"#);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "address");
+
+        let errors = field_errors(
+            r#"{"NonFungible":{"address":"458691c1692cd82facfb2c5127e36d63213448a8","id":"0xff"}}"#,
+        );
+        assert_eq!(
+            errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>(),
+            ["address", "id"]
+        );
+        assert!(errors[1]
+            .message
+            .contains("0xff is not a valid uint256 token id"));
+
+        // collection-wide ERC-1155 holdings
+        assert!(validate(
+            r#"{"Special":{"address":"0x76BE3b62873462d2142405439777e971754E8E77","id":null}}"#,
+            SECRETS
+        )
+        .is_ok());
+
+        let errors =
+            field_errors(r#"{"SplToken":{"mint":"EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"}}"#);
+        assert_eq!(
+            errors[0].to_string(),
+            "metadata: SPL tokens are not supported by evm_balance requirements"
+        );
+
+        assert!(validate(r#"{"Native":null,"block":{"Number":17000000}}"#, SECRETS).is_ok());
+
+        let errors = field_errors(r#"{"Native":null,"block":{"Hash":"0x1234"}}"#);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0]
+            .message
+            .contains("0x1234 is not a valid block hash"));
+    }
+
+    #[test]
+    fn provider_validation() {
+        let metadata = r#"{"Native":null}"#;
+        let secrets = |chunk_size: usize| {
+            format!(
+                r#"{{
+                    "rpc_url": "https://eth.public-rpc.com",
+                    "contract": "0x5BA1e12693Dc8F9c48aAD8770482f4739bEeD696",
+                    "chunk_size": {chunk_size}
+                }}"#
+            )
+        };
+
+        assert!(validate(metadata, SECRETS).is_ok());
+        assert!(validate(metadata, &secrets(100)).is_ok());
+        assert!(matches!(
+            validate(metadata, &secrets(0)),
+            Err(PluginError::Config(_))
+        ));
+        assert!(matches!(
+            validate(
+                metadata,
+                r#"{
+                    "rpc_url": "https://eth.public-rpc.com",
+                    "contract": "0x5BA1e12693Dc8F9c48aAD8770482f4739bEeD696",
+                    "log_page_size": 0
+                }"#
+            ),
+            Err(PluginError::Config(_))
+        ));
+
+        assert!(validate(
+            metadata,
+            r#"{
+                "rpc_urls": [
+                    { "url": "https://eth.public-rpc.com", "weight": 2 },
+                    { "url": "https://rpc.ankr.com/eth" }
+                ],
+                "contract": "0x5BA1e12693Dc8F9c48aAD8770482f4739bEeD696"
+            }"#
+        )
+        .is_ok());
+
+        let invalid = [
+            r#"{"contract": "0x5BA1e12693Dc8F9c48aAD8770482f4739bEeD696"}"#,
+            r#"{
+                "rpc_urls": [{ "url": "https://eth.public-rpc.com", "weight": 0 }],
+                "contract": "0x5BA1e12693Dc8F9c48aAD8770482f4739bEeD696"
+            }"#,
+            r#"{
+                "rpc_url": "https://eth.public-rpc.com",
+                "contract": "0x5BA1e12693Dc8F9c48aAD8770482f4739bEeD696",
+                "batch_size": 0
+            }"#,
+        ];
+
+        for secrets in invalid {
+            assert!(matches!(
+                validate(metadata, secrets),
+                Err(PluginError::Config(_))
+            ));
+        }
+    }
+}
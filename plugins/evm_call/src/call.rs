@@ -0,0 +1,353 @@
+use evm_balance::{
+    abi::{decode, from_hex, parse_address, AbiError, Function, ParamType, Token},
+    balance::{aggregate_calls, parse_addresses, BlockId, Call, EvmProvider, RpcError},
+};
+use guild_common::{scalar_from_units, EvmCallMetadata, ReturnType, Scalar};
+use primitive_types::{H160 as Address, U256};
+use reqwest::Client;
+use thiserror::Error;
+
+const USER_PLACEHOLDER: &str = "{user}";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CallError {
+    #[error(transparent)]
+    Abi(#[from] AbiError),
+    #[error("{signature} expects {expected} arguments, got {got}")]
+    ArgumentCount {
+        signature: String,
+        expected: usize,
+        got: usize,
+    },
+    #[error("Unsupported argument type {0}")]
+    UnsupportedType(ParamType),
+    #[error("Invalid {typ} argument {arg}")]
+    InvalidArgument { typ: ParamType, arg: String },
+}
+
+// a view function of a contract, called with the arguments of each user
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractCall {
+    pub address: Address,
+    pub function: Function,
+    pub args: Vec<String>,
+    pub returns: ReturnType,
+    pub decimals: u32,
+}
+
+impl ContractCall {
+    pub fn new(metadata: &EvmCallMetadata) -> Result<Self, CallError> {
+        let function = Function::parse(&metadata.signature)?;
+
+        if function.inputs.len() != metadata.args.len() {
+            return Err(CallError::ArgumentCount {
+                signature: function.signature(),
+                expected: function.inputs.len(),
+                got: metadata.args.len(),
+            });
+        }
+
+        let call = Self {
+            address: parse_address(metadata.address.as_str())?,
+            function,
+            args: metadata.args.clone(),
+            returns: metadata.returns,
+            decimals: metadata.decimals,
+        };
+
+        // arguments without the placeholder fail the same way for every user
+        call.encode(Address::zero())?;
+
+        Ok(call)
+    }
+
+    pub fn encode(&self, user: Address) -> Result<Vec<u8>, CallError> {
+        let user = format!("{user:?}");
+
+        let tokens = self
+            .function
+            .inputs
+            .iter()
+            .zip(&self.args)
+            .map(|(typ, arg)| parse_arg(typ, &arg.replace(USER_PLACEHOLDER, &user)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(self.function.encode_input(&tokens))
+    }
+
+    // the return value, or None if it doesn't match the return type
+    pub fn decode(&self, data: &[u8]) -> Option<Scalar> {
+        let typ = match self.returns {
+            ReturnType::Uint => ParamType::Uint(256),
+            ReturnType::Int => ParamType::Int(256),
+            ReturnType::Bool => ParamType::Bool,
+        };
+
+        let units = match decode(&[typ], data).ok()?.pop()? {
+            Token::Uint(value) => value.to_string(),
+            Token::Int(value) => to_signed(value),
+            Token::Bool(value) => return Some(Scalar::from(value as u8)),
+            _ => return None,
+        };
+
+        scalar_from_units(&units, self.decimals)
+    }
+
+    // calls the function for every address through the multicall contract,
+    // failed calls are unknown
+    pub async fn get_values(
+        &self,
+        client: &'static Client,
+        provider: &EvmProvider,
+        user_addresses: &[&str],
+        block: &BlockId,
+    ) -> Result<Vec<Option<Scalar>>, RpcError> {
+        let calls = parse_addresses(user_addresses)?
+            .into_iter()
+            .map(|user| {
+                self.encode(user)
+                    .map(|call_data| Call {
+                        target: self.address,
+                        call_data,
+                    })
+                    .map_err(|err| RpcError::Other(err.to_string()))
+            })
+            .collect::<Result<Vec<Call>, _>>()?;
+
+        let values = aggregate_calls(client, provider, &calls, block)
+            .await?
+            .into_iter()
+            .map(|data| data.and_then(|data| self.decode(&data)))
+            .collect();
+
+        Ok(values)
+    }
+}
+
+fn parse_arg(typ: &ParamType, arg: &str) -> Result<Token, CallError> {
+    let invalid = || CallError::InvalidArgument {
+        typ: typ.clone(),
+        arg: arg.to_string(),
+    };
+
+    let token = match typ {
+        ParamType::Address => Token::Address(parse_address(arg).map_err(|_| invalid())?),
+        ParamType::Uint(bits) => U256::from_dec_str(arg)
+            .ok()
+            .filter(|value| value.bits() <= *bits)
+            .map(Token::Uint)
+            .ok_or_else(invalid)?,
+        ParamType::Int(bits) => {
+            let (negative, digits) = match arg.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, arg),
+            };
+            let magnitude = U256::from_dec_str(digits).map_err(|_| invalid())?;
+            let limit = U256::one() << (bits - 1);
+
+            if negative && magnitude > limit || !negative && magnitude >= limit {
+                return Err(invalid());
+            }
+
+            if negative {
+                Token::Int((!magnitude).overflowing_add(U256::one()).0)
+            } else {
+                Token::Int(magnitude)
+            }
+        }
+        ParamType::Bool => match arg {
+            "true" => Token::Bool(true),
+            "false" => Token::Bool(false),
+            _ => return Err(invalid()),
+        },
+        ParamType::FixedBytes(len) => from_hex(arg)
+            .ok()
+            .filter(|bytes| bytes.len() == *len)
+            .map(Token::FixedBytes)
+            .ok_or_else(invalid)?,
+        ParamType::Bytes => Token::Bytes(from_hex(arg).map_err(|_| invalid())?),
+        ParamType::String => Token::String(arg.to_string()),
+        typ => return Err(CallError::UnsupportedType(typ.clone())),
+    };
+
+    Ok(token)
+}
+
+// two's complement to a signed decimal string
+fn to_signed(value: U256) -> String {
+    if value.bit(255) {
+        let magnitude = (!value).overflowing_add(U256::one()).0;
+        format!("-{magnitude}")
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CallError, ContractCall};
+    use evm_balance::{
+        abi::{encode, encode_call, parse_address, ParamType, Token},
+        balance::{BlockId, EvmProvider},
+    };
+    use guild_common::{ContractAddress, EvmCallMetadata, ReturnType, Scalar};
+    use primitive_types::U256;
+    use reqwest::Client;
+    use std::str::FromStr;
+
+    const RPC_URL: &str = "https://eth.public-rpc.com";
+    const USER_1_ADDR: &str = "0xE43878Ce78934fe8007748FF481f03B8Ee3b97DE";
+    const USER_2_ADDR: &str = "0x14DDFE8EA7FFc338015627D160ccAf99e8F16Dd3";
+    const ERC20_ADDR: &str = "0x458691c1692CD82faCfb2C5127e36D63213448A8";
+
+    fn metadata(signature: &str, args: &[&str], returns: ReturnType) -> EvmCallMetadata {
+        EvmCallMetadata {
+            address: ContractAddress::new(ERC20_ADDR).unwrap(),
+            signature: signature.to_string(),
+            args: args.iter().map(ToString::to_string).collect(),
+            returns,
+            decimals: 0,
+            block: None,
+        }
+    }
+
+    #[test]
+    fn call_encoding() {
+        let user = parse_address(USER_1_ADDR).unwrap();
+
+        let call = ContractCall::new(&metadata(
+            "balanceOfAt(address, uint256)",
+            &["{user}", "17000000"],
+            ReturnType::Uint,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            call.encode(user).unwrap(),
+            encode_call(
+                "balanceOfAt(address,uint256)",
+                &[Token::Address(user), Token::Uint(U256::from(17_000_000))]
+            )
+        );
+
+        let call = ContractCall::new(&metadata(
+            "check(int8,bool,bytes2,string)",
+            &["-128", "true", "0xbeef", "member:{user}"],
+            ReturnType::Bool,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            call.encode(user).unwrap(),
+            encode_call(
+                "check(int8,bool,bytes2,string)",
+                &[
+                    Token::Int(!U256::from(127)),
+                    Token::Bool(true),
+                    Token::FixedBytes(vec![0xbe, 0xef]),
+                    Token::String(format!("member:{user:?}")),
+                ]
+            )
+        );
+
+        let invalid = [
+            (
+                "isMember(address)",
+                vec![],
+                "isMember(address) expects 1 arguments, got 0",
+            ),
+            (
+                "isMember(address)",
+                vec!["0x1234"],
+                "Invalid address argument 0x1234",
+            ),
+            ("f(uint8)", vec!["256"], "Invalid uint8 argument 256"),
+            ("f(int8)", vec!["128"], "Invalid int8 argument 128"),
+            ("f(int8)", vec!["-129"], "Invalid int8 argument -129"),
+            (
+                "f(uint256)",
+                vec!["{user}"],
+                "Invalid uint256 argument 0x0000000000000000000000000000000000000000",
+            ),
+            (
+                "f(address[])",
+                vec!["{user}"],
+                "Unsupported argument type address[]",
+            ),
+        ];
+
+        for (signature, args, error) in invalid {
+            assert_eq!(
+                ContractCall::new(&metadata(signature, &args, ReturnType::Uint))
+                    .unwrap_err()
+                    .to_string(),
+                error
+            );
+        }
+
+        assert!(matches!(
+            ContractCall::new(&metadata("isMember", &[], ReturnType::Bool)),
+            Err(CallError::Abi(_))
+        ));
+        assert_eq!(
+            CallError::UnsupportedType(ParamType::Bytes).to_string(),
+            "Unsupported argument type bytes"
+        );
+    }
+
+    #[test]
+    fn return_decoding() {
+        let mut call = ContractCall::new(&metadata(
+            "stakedBalance(address)",
+            &["{user}"],
+            ReturnType::Uint,
+        ))
+        .unwrap();
+        call.decimals = 2;
+
+        let uint = encode(&[Token::Uint(U256::from(1_234))]);
+        assert_eq!(call.decode(&uint), Some(Scalar::from_str("12.34").unwrap()));
+        assert_eq!(call.decode(&[]), None);
+
+        call.returns = ReturnType::Int;
+        let int = encode(&[Token::Int(!U256::from(1_233))]);
+        assert_eq!(call.decode(&int), Some(Scalar::from_str("-12.34").unwrap()));
+
+        call.returns = ReturnType::Bool;
+        call.decimals = 18;
+        assert_eq!(
+            call.decode(&encode(&[Token::Bool(true)])),
+            Some(Scalar::from(1))
+        );
+        assert_eq!(call.decode(&uint), None);
+    }
+
+    #[tokio::test]
+    async fn rpc_get_values() {
+        let client: &'static Client = Box::leak(Box::new(Client::new()));
+        let provider: EvmProvider = serde_json::from_str(&format!(
+            r#"{{"rpc_url":"{RPC_URL}","contract":"0xcA11bde05977b3631167028862bE2a173976CA11","multicall":"aggregate3"}}"#
+        ))
+        .unwrap();
+
+        let mut call = ContractCall::new(&metadata(
+            "balanceOf(address)",
+            &["{user}"],
+            ReturnType::Uint,
+        ))
+        .unwrap();
+        call.decimals = 18;
+
+        assert_eq!(
+            call.get_values(
+                client,
+                &provider,
+                &[USER_1_ADDR, USER_2_ADDR],
+                &BlockId::Latest
+            )
+            .await
+            .unwrap(),
+            vec![Some(Scalar::from(0)), Some(Scalar::from(100))]
+        );
+    }
+}
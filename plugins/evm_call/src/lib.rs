@@ -0,0 +1,150 @@
+#![deny(clippy::all)]
+#![deny(clippy::dbg_macro)]
+#![allow(clippy::multiple_crate_versions)]
+#![deny(unused_crate_dependencies)]
+
+mod call;
+
+use call::ContractCall;
+use evm_balance::{
+    balance::{resolve_block, EvmProvider},
+    users::{user_addresses, values_per_user},
+    validate_provider,
+};
+use guild_common::{BlockSpec, EvmCallMetadata, PluginError, PluginOutput, User, MULTICALL3};
+use reqwest::Client;
+use serde_json::Value;
+use tokio::runtime::Runtime;
+
+fn parse_input(
+    metadata: &str,
+    secrets: &str,
+) -> Result<(EvmProvider, ContractCall, BlockSpec), PluginError> {
+    let mut secrets: Value =
+        serde_json::from_str(secrets).map_err(|err| PluginError::Config(err.to_string()))?;
+
+    // a reverting call should only fail the value of its own address, so
+    // unless configured otherwise the calls go through Multicall3's aggregate3
+    if let Some(map) = secrets.as_object_mut() {
+        map.entry("contract").or_insert_with(|| MULTICALL3.into());
+    }
+    let configured_multicall = secrets.get("multicall").is_some();
+    let provider: EvmProvider =
+        serde_json::from_value(secrets).map_err(|err| PluginError::Config(err.to_string()))?;
+
+    if !configured_multicall && !provider.contract.eq_ignore_ascii_case(MULTICALL3) {
+        return Err(PluginError::Config(format!(
+            "multicall must be set for contract {}, only Multicall3 supports the default aggregate3",
+            provider.contract
+        )));
+    }
+    let metadata: EvmCallMetadata = metadata
+        .parse()
+        .map_err(|err| PluginError::invalid("metadata", err))?;
+
+    validate_provider(&provider)?;
+
+    let call = ContractCall::new(&metadata).map_err(|err| PluginError::invalid("metadata", err))?;
+
+    // a block in the metadata takes precedence over the provider's
+    let block = metadata.block.unwrap_or_else(|| provider.block.clone());
+
+    Ok((provider, call, block))
+}
+
+#[no_mangle]
+pub fn validate(metadata: &str, secrets: &str) -> Result<(), PluginError> {
+    parse_input(metadata, secrets).map(|_| ())
+}
+
+#[no_mangle]
+pub fn retrieve(
+    client: &'static Client,
+    users: &[User],
+    metadata: &str,
+    secrets: &str,
+) -> Result<PluginOutput, PluginError> {
+    let (provider, call, block) = parse_input(metadata, secrets)?;
+
+    let addresses_with_ids = user_addresses(users);
+
+    let addresses: Vec<&str> = addresses_with_ids
+        .iter()
+        .map(|(_, address)| *address)
+        .collect();
+
+    let rt = Runtime::new().map_err(|err| PluginError::Other(err.to_string()))?;
+
+    let (values, block) = rt.block_on(async {
+        let (block, number) = resolve_block(client, &provider, &block).await?;
+
+        let values = provider
+            .get_chunked(&addresses, |chunk| {
+                call.get_values(client, &provider, chunk, &block)
+            })
+            .await?;
+
+        Ok::<_, PluginError>((values, number))
+    })?;
+
+    let res = values_per_user(
+        users,
+        &addresses_with_ids,
+        values.into_iter().map(|value| vec![value]),
+    );
+
+    Ok(PluginOutput {
+        values: res,
+        block: Some(block),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_input;
+    use evm_balance::balance::MulticallKind;
+    use guild_common::{PluginError, MULTICALL3};
+
+    #[test]
+    fn multicall_default() {
+        let metadata = r#"{
+            "address": "0x9c4b82c5b7e50b4d6ac7bd9e0fda2ebd2c00b2e3",
+            "signature": "isMember(address)",
+            "args": ["{user}"],
+            "returns": "Bool"
+        }"#;
+        let secrets =
+            |fields: &str| format!(r#"{{ "rpc_url": "https://eth.public-rpc.com" {fields} }}"#);
+        let legacy = r#", "contract": "0x5BA1e12693Dc8F9c48aAD8770482f4739bEeD696""#;
+
+        let (provider, _, _) = parse_input(metadata, &secrets("")).unwrap();
+
+        assert_eq!(provider.multicall, MulticallKind::Aggregate3);
+        assert_eq!(provider.contract, MULTICALL3);
+
+        assert!(matches!(
+            parse_input(metadata, &secrets(legacy)),
+            Err(PluginError::Config(_))
+        ));
+
+        let (provider, _, _) = parse_input(
+            metadata,
+            &secrets(&format!(r#"{legacy}, "multicall": "try_aggregate""#)),
+        )
+        .unwrap();
+
+        assert_eq!(provider.multicall, MulticallKind::TryAggregate);
+        assert_eq!(
+            provider.contract,
+            "0x5BA1e12693Dc8F9c48aAD8770482f4739bEeD696"
+        );
+
+        let (provider, _, _) = parse_input(
+            metadata,
+            &secrets(r#", "contract": "0xca11bde05977b3631167028862be2a173976ca11""#),
+        )
+        .unwrap();
+
+        assert_eq!(provider.multicall, MulticallKind::Aggregate3);
+    }
+}
@@ -0,0 +1,495 @@
+use evm_balance::{
+    abi::{decode, from_hex, ParamType, Token},
+    balance::{
+        address_topic, event_topic, get_block_timestamps, scan_logs, topic_address, EvmProvider,
+        Log, RpcError,
+    },
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use guild_common::{LogMetric, Scalar};
+use primitive_types::{H160 as Address, U256};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+const TRANSFER: &str = "Transfer(address,address,uint256)";
+
+// what the logs of a contract tell about an address
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct History {
+    pub first_acquired: Option<u64>,
+    // the block the current holding period started in
+    pub holding_since: Option<u64>,
+    pub transfers: u64,
+    pub interactions: u64,
+}
+
+struct Transfer {
+    block: u64,
+    from: Address,
+    to: Address,
+    amount: U256,
+}
+
+fn parse_transfer(log: &Log, transfer_topic: &str) -> Result<Option<Transfer>, RpcError> {
+    let is_transfer = log
+        .topics
+        .first()
+        .is_some_and(|topic| topic.eq_ignore_ascii_case(transfer_topic));
+
+    let (true, Some(from), Some(to)) = (is_transfer, log.topics.get(1), log.topics.get(2)) else {
+        return Ok(None);
+    };
+
+    // ERC-721 indexes the token id, ERC-20 puts the amount in the data
+    let amount = if log.topics.len() == 4 {
+        U256::one()
+    } else {
+        decode(&[ParamType::Uint(256)], &from_hex(&log.data)?)?
+            .pop()
+            .and_then(Token::into_uint)
+            .unwrap_or_default()
+    };
+
+    Ok(Some(Transfer {
+        block: log.position()?.0,
+        from: topic_address(from)?,
+        to: topic_address(to)?,
+        amount,
+    }))
+}
+
+// orders the logs by position and drops the ones matched by several filters
+pub fn sort_logs(logs: Vec<Log>) -> Result<Vec<Log>, RpcError> {
+    let mut logs = logs
+        .into_iter()
+        .map(|log| log.position().map(|position| (position, log)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    logs.sort_by_key(|(position, _)| *position);
+    logs.dedup_by_key(|(position, _)| *position);
+
+    Ok(logs.into_iter().map(|(_, log)| log).collect())
+}
+
+// replays the ordered logs, balances before the first log are assumed to be
+// zero
+pub fn histories(logs: &[Log], accounts: &[Address]) -> Result<Vec<History>, RpcError> {
+    let transfer_topic = event_topic(TRANSFER);
+    let mut histories = accounts
+        .iter()
+        .map(|account| (*account, (History::default(), U256::zero())))
+        .collect::<HashMap<_, _>>();
+
+    for log in logs {
+        let involved = log
+            .topics
+            .iter()
+            .skip(1)
+            .filter_map(|topic| topic_address(topic).ok())
+            .collect::<HashSet<_>>();
+
+        for account in involved {
+            if let Some((history, _)) = histories.get_mut(&account) {
+                history.interactions += 1;
+            }
+        }
+
+        let Some(transfer) = parse_transfer(log, &transfer_topic)? else {
+            continue;
+        };
+
+        if let Some((history, balance)) = histories.get_mut(&transfer.from) {
+            history.transfers += 1;
+
+            // sending to oneself doesn't change the balance
+            if transfer.from != transfer.to {
+                *balance = balance.saturating_sub(transfer.amount);
+            }
+
+            if balance.is_zero() {
+                history.holding_since = None;
+            }
+        }
+
+        if transfer.from == transfer.to || transfer.amount.is_zero() {
+            continue;
+        }
+
+        if let Some((history, balance)) = histories.get_mut(&transfer.to) {
+            history.transfers += 1;
+            history.first_acquired.get_or_insert(transfer.block);
+
+            if balance.is_zero() {
+                history.holding_since = Some(transfer.block);
+            }
+
+            *balance = balance.saturating_add(transfer.amount);
+        }
+    }
+
+    let histories = accounts
+        .iter()
+        .map(|account| histories[account].0.clone())
+        .collect();
+
+    Ok(histories)
+}
+
+// the metric of each address, holding durations are measured in seconds up
+// to the timestamp of the evaluated block
+pub fn metric_values(
+    metric: LogMetric,
+    histories: &[History],
+    timestamps: &HashMap<u64, u64>,
+    now: u64,
+) -> Vec<Vec<Option<Scalar>>> {
+    histories
+        .iter()
+        .map(|history| match metric {
+            LogMetric::FirstAcquired => history
+                .first_acquired
+                .map(|block| Some(Scalar::from(block)))
+                .into_iter()
+                .collect(),
+            LogMetric::HoldingDuration => {
+                let duration = history
+                    .holding_since
+                    .map(|block| {
+                        timestamps
+                            .get(&block)
+                            .map(|since| Scalar::from(now.saturating_sub(*since)))
+                    })
+                    .unwrap_or(Some(Scalar::from(0)));
+
+                vec![duration]
+            }
+            LogMetric::TransferCount => vec![Some(Scalar::from(history.transfers))],
+            LogMetric::InteractionCount => vec![Some(Scalar::from(history.interactions))],
+        })
+        .collect()
+}
+
+fn filters(metric: LogMetric, contract: &str, accounts: &[Address]) -> Vec<Value> {
+    let accounts = accounts.iter().map(address_topic).collect::<Vec<_>>();
+
+    let topics = match metric {
+        LogMetric::InteractionCount => vec![
+            json!([null, accounts]),
+            json!([null, null, accounts]),
+            json!([null, null, null, accounts]),
+        ],
+        _ => {
+            let transfer = event_topic(TRANSFER);
+
+            vec![
+                json!([transfer, accounts]),
+                json!([transfer, null, accounts]),
+            ]
+        }
+    };
+
+    topics
+        .into_iter()
+        .map(|topics| json!({ "address": contract, "topics": topics }))
+        .collect()
+}
+
+// scans the logs of the contract between the blocks and computes the metric
+// of every address
+pub async fn get_metric_values(
+    client: &'static Client,
+    provider: &EvmProvider,
+    contract: &str,
+    metric: LogMetric,
+    accounts: &[Address],
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<Vec<Option<Scalar>>>, RpcError> {
+    let filters = accounts
+        .chunks(provider.chunk_size)
+        .flat_map(|chunk| filters(metric, contract, chunk))
+        .collect::<Vec<_>>();
+
+    let logs: Vec<Vec<Log>> = stream::iter(&filters)
+        .map(|filter| scan_logs(client, provider, filter, from_block, to_block))
+        .buffered(provider.max_concurrency)
+        .try_collect()
+        .await?;
+
+    let histories = histories(&sort_logs(logs.concat())?, accounts)?;
+
+    let mut blocks = BTreeSet::new();
+
+    if metric == LogMetric::HoldingDuration {
+        blocks.extend(histories.iter().filter_map(|history| history.holding_since));
+        blocks.insert(to_block);
+    }
+
+    let blocks = blocks.into_iter().collect::<Vec<_>>();
+    let timestamps = get_block_timestamps(client, provider, &blocks)
+        .await?
+        .into_iter()
+        .zip(&blocks)
+        .map(|(timestamp, block)| (*block, timestamp))
+        .collect::<HashMap<_, _>>();
+
+    let now = timestamps.get(&to_block).copied().unwrap_or_default();
+
+    Ok(metric_values(metric, &histories, &timestamps, now))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{histories, metric_values, sort_logs, History, TRANSFER};
+    use evm_balance::{
+        abi::{encode, parse_address, to_hex, Token},
+        balance::{address_topic, event_topic, Log},
+    };
+    use guild_common::{LogMetric, Scalar};
+    use primitive_types::{H160 as Address, U256};
+    use std::collections::HashMap;
+
+    fn log(topics: Vec<String>, data: &[Token], block: u64, index: u64) -> Log {
+        Log {
+            topics,
+            data: to_hex(&encode(data)),
+            block_number: format!("{block:#x}"),
+            log_index: format!("{index:#x}"),
+        }
+    }
+
+    fn transfer(from: &Address, to: &Address, amount: u64, block: u64, index: u64) -> Log {
+        log(
+            vec![
+                event_topic(TRANSFER),
+                address_topic(from),
+                address_topic(to),
+            ],
+            &[Token::Uint(U256::from(amount))],
+            block,
+            index,
+        )
+    }
+
+    #[test]
+    fn log_histories() {
+        let user_1 = parse_address("0xe43878ce78934fe8007748ff481f03b8ee3b97de").unwrap();
+        let user_2 = parse_address("0x14ddfe8ea7ffc338015627d160ccaf99e8f16dd3").unwrap();
+        let user_3 = parse_address("0x283d678711daa088640c86a1ad3f12c00ec1252e").unwrap();
+        let mint = Address::zero();
+
+        let nft_transfer = log(
+            vec![
+                event_topic(TRANSFER),
+                address_topic(&mint),
+                address_topic(&user_2),
+                to_hex(&encode(&[Token::Uint(U256::from(42))])),
+            ],
+            &[],
+            30,
+            0,
+        );
+        let vote = log(
+            vec![
+                event_topic("Voted(address,uint256)"),
+                address_topic(&user_3),
+            ],
+            &[Token::Uint(U256::from(1))],
+            35,
+            2,
+        );
+
+        // fetched by two filters and out of order
+        let logs = sort_logs(vec![
+            transfer(&user_1, &user_2, 5, 20, 1),
+            transfer(&mint, &user_1, 10, 10, 0),
+            vote.clone(),
+            transfer(&user_2, &user_1, 5, 40, 0),
+            nft_transfer,
+            transfer(&user_1, &user_1, 7, 25, 3),
+            transfer(&user_1, &user_2, 5, 20, 1),
+            vote,
+        ])
+        .unwrap();
+
+        assert_eq!(logs.len(), 6);
+
+        let histories = histories(&logs, &[user_1, user_2, user_3]).unwrap();
+
+        assert_eq!(
+            histories,
+            vec![
+                History {
+                    first_acquired: Some(10),
+                    holding_since: Some(10),
+                    transfers: 4,
+                    interactions: 4,
+                },
+                History {
+                    first_acquired: Some(20),
+                    holding_since: Some(20),
+                    transfers: 3,
+                    interactions: 3,
+                },
+                History {
+                    first_acquired: None,
+                    holding_since: None,
+                    transfers: 0,
+                    interactions: 1,
+                },
+            ]
+        );
+
+        let timestamps = HashMap::from([(10, 1_000), (20, 1_200)]);
+        let values = |metric| metric_values(metric, &histories, &timestamps, 2_000);
+
+        assert_eq!(
+            values(LogMetric::FirstAcquired),
+            vec![
+                vec![Some(Scalar::from(10))],
+                vec![Some(Scalar::from(20))],
+                vec![]
+            ]
+        );
+        assert_eq!(
+            values(LogMetric::HoldingDuration),
+            vec![
+                vec![Some(Scalar::from(1_000))],
+                vec![Some(Scalar::from(800))],
+                vec![Some(Scalar::from(0))]
+            ]
+        );
+        assert_eq!(
+            values(LogMetric::TransferCount),
+            vec![
+                vec![Some(Scalar::from(4))],
+                vec![Some(Scalar::from(3))],
+                vec![Some(Scalar::from(0))]
+            ]
+        );
+        assert_eq!(
+            values(LogMetric::InteractionCount)[2],
+            vec![Some(Scalar::from(1))]
+        );
+
+        // selling everything ends the holding period
+        let sold = [
+            logs.clone(),
+            vec![transfer(&user_1, &user_3, 4, 50, 0)],
+            vec![transfer(&user_2, &user_3, 1, 51, 0)],
+        ]
+        .concat();
+        let histories = super::histories(&sold, &[user_1, user_2, user_3]).unwrap();
+
+        assert_eq!(histories[0].holding_since, Some(10));
+        assert_eq!(histories[1].holding_since, None);
+        assert_eq!(histories[2].holding_since, Some(50));
+        assert_eq!(histories[2].first_acquired, Some(50));
+    }
+
+    #[test]
+    fn holding_after_buy_back() {
+        let user = parse_address("0xe43878ce78934fe8007748ff481f03b8ee3b97de").unwrap();
+        let other = parse_address("0x14ddfe8ea7ffc338015627d160ccaf99e8f16dd3").unwrap();
+        let mint = Address::zero();
+
+        let logs = vec![
+            transfer(&mint, &user, 10, 10, 0),
+            transfer(&user, &other, 4, 15, 0),
+            transfer(&user, &other, 6, 20, 0),
+            transfer(&other, &user, 3, 30, 0),
+            transfer(&other, &user, 2, 35, 0),
+        ];
+
+        let histories = histories(&logs, &[user]).unwrap();
+
+        // the period restarts at the buy back and not at the first purchase
+        assert_eq!(
+            histories,
+            vec![History {
+                first_acquired: Some(10),
+                holding_since: Some(30),
+                transfers: 5,
+                interactions: 5,
+            }]
+        );
+
+        let timestamps = HashMap::from([(10, 1_000), (30, 1_600)]);
+
+        assert_eq!(
+            metric_values(LogMetric::HoldingDuration, &histories, &timestamps, 2_000),
+            vec![vec![Some(Scalar::from(400))]]
+        );
+
+        // selling everything again leaves no holding period
+        let sold = [logs, vec![transfer(&user, &other, 5, 40, 0)]].concat();
+        let histories = super::histories(&sold, &[user]).unwrap();
+
+        assert_eq!(histories[0].holding_since, None);
+        assert_eq!(
+            metric_values(LogMetric::HoldingDuration, &histories, &timestamps, 2_000),
+            vec![vec![Some(Scalar::from(0))]]
+        );
+    }
+
+    #[test]
+    fn interaction_counts() {
+        let user_1 = parse_address("0xe43878ce78934fe8007748ff481f03b8ee3b97de").unwrap();
+        let user_2 = parse_address("0x14ddfe8ea7ffc338015627d160ccaf99e8f16dd3").unwrap();
+        let user_3 = parse_address("0x283d678711daa088640c86a1ad3f12c00ec1252e").unwrap();
+
+        let logs = vec![
+            log(
+                vec![
+                    event_topic("Voted(address,uint256)"),
+                    address_topic(&user_1),
+                ],
+                &[Token::Uint(U256::from(1))],
+                10,
+                0,
+            ),
+            // naming the address in several topics is still one interaction
+            log(
+                vec![
+                    event_topic("Approval(address,address,uint256)"),
+                    address_topic(&user_1),
+                    address_topic(&user_1),
+                ],
+                &[Token::Uint(U256::from(1))],
+                11,
+                0,
+            ),
+            log(
+                vec![
+                    event_topic("Delegated(address,address,address)"),
+                    address_topic(&user_3),
+                    address_topic(&user_3),
+                    address_topic(&user_2),
+                ],
+                &[],
+                12,
+                0,
+            ),
+            // unindexed arguments aren't matched by the log filters
+            log(
+                vec![event_topic("Registered(address)")],
+                &[Token::Address(user_2)],
+                13,
+                0,
+            ),
+        ];
+
+        let histories = histories(&logs, &[user_1, user_2, user_3]).unwrap();
+
+        assert_eq!(
+            metric_values(LogMetric::InteractionCount, &histories, &HashMap::new(), 0),
+            vec![
+                vec![Some(Scalar::from(2))],
+                vec![Some(Scalar::from(1))],
+                vec![Some(Scalar::from(1))]
+            ]
+        );
+        assert!(histories.iter().all(|history| history.transfers == 0));
+    }
+}
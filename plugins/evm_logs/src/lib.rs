@@ -0,0 +1,86 @@
+#![deny(clippy::all)]
+#![deny(clippy::dbg_macro)]
+#![allow(clippy::multiple_crate_versions)]
+#![deny(unused_crate_dependencies)]
+
+mod history;
+
+use evm_balance::{
+    balance::{parse_addresses, resolve_block, EvmProvider},
+    users::{user_addresses, values_per_user},
+    validate_provider,
+};
+use guild_common::{BlockSpec, EvmLogsMetadata, PluginError, PluginOutput, User};
+use history::get_metric_values;
+use reqwest::Client;
+use tokio::runtime::Runtime;
+
+fn parse_input(
+    metadata: &str,
+    secrets: &str,
+) -> Result<(EvmProvider, EvmLogsMetadata, BlockSpec), PluginError> {
+    let provider: EvmProvider =
+        serde_json::from_str(secrets).map_err(|err| PluginError::Config(err.to_string()))?;
+    let metadata: EvmLogsMetadata = metadata
+        .parse()
+        .map_err(|err| PluginError::invalid("metadata", err))?;
+
+    validate_provider(&provider)?;
+
+    // a block in the metadata takes precedence over the provider's
+    let block = metadata
+        .block
+        .clone()
+        .unwrap_or_else(|| provider.block.clone());
+
+    Ok((provider, metadata, block))
+}
+
+#[no_mangle]
+pub fn validate(metadata: &str, secrets: &str) -> Result<(), PluginError> {
+    parse_input(metadata, secrets).map(|_| ())
+}
+
+#[no_mangle]
+pub fn retrieve(
+    client: &'static Client,
+    users: &[User],
+    metadata: &str,
+    secrets: &str,
+) -> Result<PluginOutput, PluginError> {
+    let (provider, metadata, block) = parse_input(metadata, secrets)?;
+
+    let addresses_with_ids = user_addresses(users);
+
+    let addresses: Vec<&str> = addresses_with_ids
+        .iter()
+        .map(|(_, address)| *address)
+        .collect();
+
+    let rt = Runtime::new().map_err(|err| PluginError::Other(err.to_string()))?;
+
+    let (values, block) = rt.block_on(async {
+        let (_, number) = resolve_block(client, &provider, &block).await?;
+        let accounts = parse_addresses(&addresses)?;
+
+        let values = get_metric_values(
+            client,
+            &provider,
+            metadata.address.as_str(),
+            metadata.metric,
+            &accounts,
+            metadata.from_block,
+            number,
+        )
+        .await?;
+
+        Ok::<_, PluginError>((values, number))
+    })?;
+
+    let res = values_per_user(users, &addresses_with_ids, values);
+
+    Ok(PluginOutput {
+        values: res,
+        block: Some(block),
+    })
+}
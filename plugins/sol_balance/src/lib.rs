@@ -3,7 +3,7 @@
 #![allow(clippy::multiple_crate_versions)]
 #![deny(unused_crate_dependencies)]
 
-use guild_common::{Scalar, User};
+use guild_common::{PluginError, PluginOutput, Scalar, User};
 use reqwest::Client;
 use serde_json::{json, Value};
 use thiserror::Error;
@@ -17,6 +17,12 @@ pub enum SolanaError {
     Other(String),
 }
 
+impl From<SolanaError> for PluginError {
+    fn from(error: SolanaError) -> Self {
+        PluginError::Rpc(error.to_string())
+    }
+}
+
 fn create_payload(method: &str, params: Value, id: u32) -> Value {
     json!({
         "method"  : method,
@@ -51,7 +57,12 @@ async fn get_balance_batch(
         Some(values) => {
             let res = values
                 .iter()
-                .map(|value| value["lamports"].as_f64().unwrap_or_default())
+                .map(|value| {
+                    value["lamports"]
+                        .as_u64()
+                        .map(Scalar::from)
+                        .unwrap_or_default()
+                })
                 .collect();
 
             Ok(res)
@@ -62,15 +73,36 @@ async fn get_balance_batch(
     }
 }
 
+fn parse_base_url(secrets: &str) -> Result<String, PluginError> {
+    let secret: Value =
+        serde_json::from_str(secrets).map_err(|err| PluginError::Config(err.to_string()))?;
+
+    secret
+        .as_str()
+        .map(|base_url| base_url.to_string())
+        .ok_or_else(|| PluginError::Config("expected an rpc url".to_string()))
+}
+
+#[no_mangle]
+pub fn validate(metadata: &str, secrets: &str) -> Result<(), PluginError> {
+    if !metadata.is_empty() {
+        return Err(PluginError::invalid(
+            "metadata",
+            "sol_balance does not take any metadata",
+        ));
+    }
+
+    parse_base_url(secrets).map(|_| ())
+}
+
 #[no_mangle]
 pub fn retrieve(
     client: &'static Client,
     users: &[User],
     _metadata: &str,
     secrets: &str,
-) -> Result<Vec<Vec<Scalar>>, Box<dyn std::error::Error>> {
-    let secret: Value = serde_json::from_str(secrets)?;
-    let base_url = secret.as_str().unwrap_or_default();
+) -> Result<PluginOutput, PluginError> {
+    let base_url = parse_base_url(secrets)?;
 
     let pubkeys_with_ids: Vec<(u64, &str)> = users
         .iter()
@@ -88,14 +120,14 @@ pub fn retrieve(
 
     let pubkeys: Vec<&str> = pubkeys_with_ids.iter().map(|(_, pubkey)| *pubkey).collect();
 
-    let rt = Runtime::new()?;
+    let rt = Runtime::new().map_err(|err| PluginError::Other(err.to_string()))?;
 
-    let balances: Vec<_> = rt.block_on(get_balance_batch(client, base_url, &pubkeys))?;
+    let balances: Vec<_> = rt.block_on(get_balance_batch(client, &base_url, &pubkeys))?;
 
     let id_balances = pubkeys_with_ids
         .iter()
         .zip(balances.iter())
-        .map(|((user_id, _), balance)| (*user_id, *balance))
+        .map(|((user_id, _), balance)| (*user_id, balance.clone()))
         .collect::<Vec<(u64, Scalar)>>();
 
     let res = users
@@ -103,17 +135,26 @@ pub fn retrieve(
         .map(|user| {
             id_balances
                 .iter()
-                .filter_map(|(i, balance)| if &user.id == i { Some(*balance) } else { None })
+                .filter_map(|(i, balance)| {
+                    if &user.id == i {
+                        Some(Some(balance.clone()))
+                    } else {
+                        None
+                    }
+                })
                 .collect()
         })
         .collect();
 
-    Ok(res)
+    Ok(PluginOutput {
+        values: res,
+        block: None,
+    })
 }
 
 #[cfg(test)]
 mod test {
-    use super::get_balance_batch;
+    use super::{get_balance_batch, validate, Scalar};
 
     const BASE_URL: &str = "https://api.mainnet-beta.solana.com";
 
@@ -128,6 +169,15 @@ mod test {
 
         let res = get_balance_batch(&client, BASE_URL, pubkeys).await.unwrap();
 
-        assert_eq!(res, [1761523130.0, 2000000.0]);
+        assert_eq!(res, [Scalar::from(1761523130), Scalar::from(2000000)]);
+    }
+
+    #[test]
+    fn validate_test() {
+        let secrets = format!("\"{BASE_URL}\"");
+
+        assert!(validate("", &secrets).is_ok());
+        assert!(validate("{\"Native\":null}", &secrets).is_err());
+        assert!(validate("", "{}").is_err());
     }
 }
@@ -5,26 +5,44 @@
 
 use config::{Config, File};
 pub use db::RedisCache;
-use guild_common::{Relation, Scalar, User};
+use guild_common::{
+    join_fields, Aggregation, Chain, ChainFamily, ChainInfo, ChainRegistry, FieldError,
+    IdentityScope, PluginError, PluginOutput, Relation, Scalar, User,
+};
 use libloading::{Library, Symbol};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
 use std::{collections::HashMap, path::Path};
 use thiserror::Error;
+pub use typed::{RequirementKind, TypedRequirement};
 
 mod db;
+mod typed;
 
-type Data = Vec<Vec<Scalar>>;
-type Error = Box<dyn std::error::Error>;
+type Retrieve = fn(&Client, &[User], &str, &str) -> Result<PluginOutput, PluginError>;
+type Validate = fn(&str, &str) -> Result<(), PluginError>;
 
 #[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Requirement {
     pub id: String,
     pub typ: String,
     pub config_key: String,
     pub metadata: String,
     pub relation: Relation<Scalar>,
+    #[serde(default)]
+    pub aggregation: Aggregation,
+    #[serde(default)]
+    pub identity_scope: IdentityScope,
+}
+
+// the outcome of a requirement for each user, and the block the plugin read
+// the data at, if it pins one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evaluation {
+    pub accesses: Vec<bool>,
+    pub block: Option<u64>,
 }
 
 #[derive(Error, Debug)]
@@ -33,19 +51,89 @@ pub enum ConfigError {
     Config(#[from] config::ConfigError),
     #[error("Value not found for key {0}")]
     NoSuchEntry(String),
+    #[error("Invalid value for key {0}: {1}")]
+    InvalidEntry(String, String),
+}
+
+#[derive(Error, Debug)]
+pub enum RequirementError {
+    #[error("Requirement {id}: {source}")]
+    Config {
+        id: String,
+        source: Box<ConfigError>,
+        retryable: bool,
+    },
+    #[error("Requirement {id}: failed to load plugin: {source}")]
+    PluginLoad {
+        id: String,
+        source: libloading::Error,
+        retryable: bool,
+    },
+    #[error("Requirement {id}: failed to resolve symbol `{symbol}`: {source}")]
+    Symbol {
+        id: String,
+        symbol: String,
+        source: libloading::Error,
+        retryable: bool,
+    },
+    #[error("Requirement {id}: {source}")]
+    Execution {
+        id: String,
+        source: PluginError,
+        retryable: bool,
+    },
+    #[error("Requirement {id}: invalid input: {}", join_fields(.errors))]
+    InvalidInput {
+        id: String,
+        errors: Vec<FieldError>,
+        retryable: bool,
+    },
+}
+
+impl RequirementError {
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Config { id, .. }
+            | Self::PluginLoad { id, .. }
+            | Self::Symbol { id, .. }
+            | Self::Execution { id, .. }
+            | Self::InvalidInput { id, .. } => id,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Config { retryable, .. }
+            | Self::PluginLoad { retryable, .. }
+            | Self::Symbol { retryable, .. }
+            | Self::Execution { retryable, .. }
+            | Self::InvalidInput { retryable, .. } => *retryable,
+        }
+    }
 }
 
 const CONFIG_PATH: &str = "config.json";
+const CHAINS_KEY: &str = "chains";
+
+fn config_path() -> String {
+    std::env::var("CONFIG_PATH").unwrap_or(CONFIG_PATH.to_string())
+}
 
 fn read_config(redis_cache: &mut RedisCache, key: &str) -> Result<Value, ConfigError> {
+    read_config_at(redis_cache, &config_path(), key)
+}
+
+fn read_config_at(
+    redis_cache: &mut RedisCache,
+    config_path: &str,
+    key: &str,
+) -> Result<Value, ConfigError> {
     if let Some(value) = redis_cache.read(key) {
         return Ok(value);
     }
 
-    let config_path = std::env::var("CONFIG_PATH").unwrap_or(CONFIG_PATH.to_string());
-
     let settings = Config::builder()
-        .add_source(File::from(Path::new(&config_path)))
+        .add_source(File::from(Path::new(config_path)))
         .build()?;
 
     let map = settings.try_deserialize::<HashMap<String, Value>>()?;
@@ -59,39 +147,227 @@ fn read_config(redis_cache: &mut RedisCache, key: &str) -> Result<Value, ConfigE
     }
 }
 
+pub fn read_chain_registry(redis_cache: &mut RedisCache) -> Result<ChainRegistry, ConfigError> {
+    read_chain_registry_at(redis_cache, &config_path())
+}
+
+fn read_chain_registry_at(
+    redis_cache: &mut RedisCache,
+    config_path: &str,
+) -> Result<ChainRegistry, ConfigError> {
+    let mut registry = ChainRegistry::builtin();
+
+    match read_config_at(redis_cache, config_path, CHAINS_KEY) {
+        Ok(value) => {
+            registry.extend(serde_json::from_value(value).map_err(|err| {
+                ConfigError::InvalidEntry(CHAINS_KEY.to_string(), err.to_string())
+            })?)
+        }
+        Err(ConfigError::NoSuchEntry(_)) => {}
+        Err(err) => return Err(err),
+    }
+
+    Ok(registry)
+}
+
+// fills the endpoints and the multicall contract of a chain that the config
+// entry leaves unset from the registry
+fn with_chain_defaults(secrets: Option<Value>, info: &ChainInfo) -> Option<Value> {
+    match info.family {
+        ChainFamily::Evm => {
+            let mut secrets = secrets.unwrap_or_else(|| json!({}));
+
+            if let Some(map) = secrets.as_object_mut() {
+                if !map.contains_key("rpc_url")
+                    && !map.contains_key("rpc_urls")
+                    && !info.rpc_urls.is_empty()
+                {
+                    map.insert("rpc_urls".to_string(), json!(info.rpc_urls));
+                }
+                if let (false, Some(multicall)) = (map.contains_key("contract"), &info.multicall) {
+                    map.insert("contract".to_string(), json!(multicall));
+                }
+            }
+
+            Some(secrets)
+        }
+        ChainFamily::Solana => secrets.or_else(|| info.rpc_urls.first().map(|url| json!(url))),
+    }
+}
+
 impl Requirement {
+    pub fn new(
+        id: &str,
+        typ: &str,
+        config_key: &str,
+        metadata: &str,
+        relation: Relation<Scalar>,
+        redis_cache: &mut RedisCache,
+    ) -> Result<Self, RequirementError> {
+        let requirement = Self {
+            id: id.to_string(),
+            typ: typ.to_string(),
+            config_key: config_key.to_string(),
+            metadata: metadata.to_string(),
+            relation,
+            aggregation: Aggregation::default(),
+            identity_scope: IdentityScope::default(),
+        };
+
+        requirement.validate(redis_cache)?;
+
+        Ok(requirement)
+    }
+
+    fn config_error(&self, source: ConfigError) -> RequirementError {
+        RequirementError::Config {
+            id: self.id.clone(),
+            source: Box::new(source),
+            retryable: false,
+        }
+    }
+
+    fn plugin_error(&self, source: PluginError) -> RequirementError {
+        match source {
+            PluginError::Config(reason) => {
+                self.config_error(ConfigError::InvalidEntry(self.config_key.clone(), reason))
+            }
+            PluginError::InvalidInput(errors) => RequirementError::InvalidInput {
+                id: self.id.clone(),
+                errors,
+                retryable: false,
+            },
+            source => RequirementError::Execution {
+                id: self.id.clone(),
+                retryable: source.is_retryable(),
+                source,
+            },
+        }
+    }
+
+    fn load_plugin(&self, redis_cache: &mut RedisCache) -> Result<Library, RequirementError> {
+        let path = read_config(redis_cache, &self.typ.to_string())
+            .map_err(|err| self.config_error(err))?;
+        let path_str = path.as_str().ok_or_else(|| {
+            self.config_error(ConfigError::InvalidEntry(
+                self.typ.clone(),
+                "expected a path to the plugin".to_string(),
+            ))
+        })?;
+
+        unsafe { Library::new(path_str) }.map_err(|source| RequirementError::PluginLoad {
+            id: self.id.clone(),
+            source,
+            retryable: false,
+        })
+    }
+
+    fn symbol<'lib, T>(
+        &self,
+        lib: &'lib Library,
+        name: &str,
+    ) -> Result<Symbol<'lib, T>, RequirementError> {
+        unsafe { lib.get(name.as_bytes()) }.map_err(|source| RequirementError::Symbol {
+            id: self.id.clone(),
+            symbol: name.to_string(),
+            source,
+            retryable: false,
+        })
+    }
+
+    fn secrets(&self, redis_cache: &mut RedisCache) -> Result<String, RequirementError> {
+        let secrets = match read_config(redis_cache, &self.config_key) {
+            Ok(secrets) => Some(secrets),
+            Err(ConfigError::NoSuchEntry(_)) => None,
+            Err(err) => return Err(self.config_error(err)),
+        };
+
+        let registry = read_chain_registry(redis_cache).map_err(|err| self.config_error(err))?;
+        let secrets = match self.config_key.parse::<Chain>() {
+            Ok(chain) => match registry.get(&chain) {
+                Some(info) => with_chain_defaults(secrets, info),
+                None => secrets,
+            },
+            Err(_) => secrets,
+        };
+
+        secrets
+            .map(|secrets| secrets.to_string())
+            .ok_or_else(|| self.config_error(ConfigError::NoSuchEntry(self.config_key.clone())))
+    }
+
+    fn validate_with(&self, lib: &Library, secrets: &str) -> Result<(), RequirementError> {
+        let validate: Symbol<Validate> = self.symbol(lib, "validate")?;
+
+        validate(&self.metadata, secrets).map_err(|err| self.plugin_error(err))
+    }
+
+    pub fn validate(&self, redis_cache: &mut RedisCache) -> Result<(), RequirementError> {
+        let lib = self.load_plugin(redis_cache)?;
+        let secrets = self.secrets(redis_cache)?;
+
+        self.validate_with(&lib, &secrets)
+    }
+
     pub fn check(
         &self,
         redis_cache: &mut RedisCache,
         client: &Client,
         users: &[User],
-    ) -> Result<Vec<bool>, Error> {
-        let path = read_config(redis_cache, &self.typ.to_string())?;
-        let path_str = path.as_str().unwrap_or_default();
+    ) -> Result<Vec<bool>, RequirementError> {
+        self.evaluate(redis_cache, client, users)
+            .map(|evaluation| evaluation.accesses)
+    }
 
-        let lib = unsafe { Library::new(path_str) }?;
+    pub fn evaluate(
+        &self,
+        redis_cache: &mut RedisCache,
+        client: &Client,
+        users: &[User],
+    ) -> Result<Evaluation, RequirementError> {
+        let lib = self.load_plugin(redis_cache)?;
+        let secrets = self.secrets(redis_cache)?;
 
-        let retrieve: Symbol<extern "C" fn(&Client, &[User], &str, &str) -> Result<Data, Error>> =
-            unsafe { lib.get(b"retrieve") }?;
+        self.validate_with(&lib, &secrets)?;
 
-        let secrets = read_config(redis_cache, &self.config_key)?;
+        let retrieve: Symbol<Retrieve> = self.symbol(&lib, "retrieve")?;
 
-        let data = retrieve(client, users, &self.metadata, &secrets.to_string())?;
+        let output = match self.identity_scope {
+            IdentityScope::All => retrieve(client, users, &self.metadata, &secrets),
+            IdentityScope::Primary => {
+                let users = users
+                    .iter()
+                    .map(User::with_primary_identities)
+                    .collect::<Vec<_>>();
+                retrieve(client, &users, &self.metadata, &secrets)
+            }
+        }
+        .map_err(|err| self.plugin_error(err))?;
 
-        let res = data
+        let accesses = output
+            .values
             .iter()
-            .map(|values| values.iter().any(|v| self.relation.assert(v)))
+            .map(|values| self.aggregation.assert_partial(&self.relation, values))
             .collect();
 
-        Ok(res)
+        Ok(Evaluation {
+            accesses,
+            block: output.block,
+        })
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{RedisCache, Requirement, User};
-    use guild_common::{Chain, Relation, RequirementType, TokenType};
+    use super::{
+        read_chain_registry_at, with_chain_defaults, ConfigError, RedisCache, Requirement,
+        RequirementError, RequirementKind, TypedRequirement, User,
+    };
+    use guild_common::{
+        Aggregation, Chain, ContractAddress, IdentityScope, Relation, Scalar, TokenType,
+    };
     use reqwest::Client;
+    use serde_json::json;
     use tokio::runtime;
 
     const USERS: &str = r#"[
@@ -120,29 +396,37 @@ mod test {
 
     #[test]
     fn requirement_check() {
-        let token_type = TokenType::Fungible {
-            address: "0x458691c1692cd82facfb2c5127e36d63213448a8".to_string(),
+        let token_type = TokenType::Erc20 {
+            address: ContractAddress::new("0x458691c1692cd82facfb2c5127e36d63213448a8").unwrap(),
         };
 
-        let relation_1 = Relation::GreaterThan(0.0);
+        let relation_1 = Relation::GreaterThan(Scalar::from(0));
 
-        let evm_balance = Requirement {
+        let evm_balance: Requirement = TypedRequirement {
             id: "69".to_string(),
-            typ: RequirementType::EvmBalance.to_string(),
-            config_key: Chain::Ethereum.to_string(),
-            metadata: serde_json::to_string(&token_type).unwrap(),
+            kind: RequirementKind::EvmBalance {
+                chain: Chain::Ethereum,
+                token: token_type,
+                block: None,
+            },
             relation: relation_1,
-        };
+            aggregation: Aggregation::default(),
+            identity_scope: IdentityScope::default(),
+        }
+        .into();
 
-        let relation_2 = Relation::GreaterThan(420.0);
+        let relation_2 = Relation::GreaterThan(Scalar::from(420));
 
-        let sol_balance = Requirement {
+        let sol_balance: Requirement = TypedRequirement {
             id: "99".to_string(),
-            typ: RequirementType::SolBalance.to_string(),
-            config_key: Chain::SolanaMain.to_string(),
-            metadata: String::new(),
+            kind: RequirementKind::SolBalance {
+                chain: Chain::SolanaMain,
+            },
             relation: relation_2,
-        };
+            aggregation: Aggregation::default(),
+            identity_scope: IdentityScope::default(),
+        }
+        .into();
 
         let mut redis_cache = RedisCache::default();
         let client = Client::new();
@@ -151,19 +435,107 @@ mod test {
         let rt = runtime::Runtime::new().unwrap();
 
         rt.block_on(async {
-            assert_eq!(
-                evm_balance
-                    .check(&mut redis_cache, &client, &users)
-                    .unwrap(),
-                vec![false, true, false]
-            );
-
-            assert_eq!(
-                sol_balance
-                    .check(&mut redis_cache, &client, &users)
-                    .unwrap(),
-                vec![true, true, false]
-            );
+            let evaluation = evm_balance
+                .evaluate(&mut redis_cache, &client, &users)
+                .unwrap();
+
+            assert_eq!(evaluation.accesses, vec![false, true, false]);
+            assert!(evaluation.block.is_some());
+
+            let evaluation = sol_balance
+                .evaluate(&mut redis_cache, &client, &users)
+                .unwrap();
+
+            assert_eq!(evaluation.accesses, vec![true, true, false]);
+            assert_eq!(evaluation.block, None);
         });
     }
+
+    #[test]
+    fn requirement_error() {
+        let req = Requirement {
+            id: "420".to_string(),
+            typ: "no_such_plugin".to_string(),
+            config_key: "no_such_chain".to_string(),
+            metadata: String::new(),
+            relation: Relation::GreaterThan(Scalar::from(0)),
+            aggregation: Aggregation::default(),
+            identity_scope: IdentityScope::default(),
+        };
+
+        let mut redis_cache = RedisCache::default();
+        let client = Client::new();
+
+        let err = req.check(&mut redis_cache, &client, &[]).unwrap_err();
+
+        assert!(matches!(err, RequirementError::Config { .. }));
+        assert_eq!(err.id(), "420");
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn chain_registry_config() {
+        let dir = std::env::temp_dir().join(format!("guild-chains-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+        let path = path.to_str().unwrap();
+        let mut redis_cache = RedisCache { conn: None };
+
+        std::fs::write(path, r#"{ "ethereum": { "rpc_url": "http://localhost" } }"#).unwrap();
+
+        let registry = read_chain_registry_at(&mut redis_cache, path).unwrap();
+
+        assert_eq!(registry.chains().count(), Chain::BUILTIN.len());
+
+        std::fs::write(
+            path,
+            r#"{
+                "chains": {
+                    "base": {
+                        "chain_id": 8453,
+                        "family": "evm",
+                        "native_symbol": "ETH",
+                        "native_decimals": 18,
+                        "multicall": "0xcA11bde05977b3631167028862bE2a173976CA11",
+                        "rpc_urls": ["https://mainnet.base.org"]
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let registry = read_chain_registry_at(&mut redis_cache, path).unwrap();
+        let base = registry.get(&"base".parse().unwrap()).unwrap();
+
+        assert_eq!(
+            with_chain_defaults(None, base),
+            Some(json!({
+                "rpc_urls": ["https://mainnet.base.org"],
+                "contract": "0xcA11bde05977b3631167028862bE2a173976CA11"
+            }))
+        );
+        assert_eq!(
+            with_chain_defaults(Some(json!({ "rpc_url": "http://localhost" })), base),
+            Some(json!({
+                "rpc_url": "http://localhost",
+                "contract": "0xcA11bde05977b3631167028862bE2a173976CA11"
+            }))
+        );
+
+        let solana = registry.get(&Chain::SolanaDev).unwrap();
+
+        assert_eq!(
+            with_chain_defaults(None, solana),
+            Some(json!("https://api.devnet.solana.com"))
+        );
+
+        std::fs::write(path, r#"{ "chains": { "base": { "chain_id": 8453 } } }"#).unwrap();
+
+        assert!(matches!(
+            read_chain_registry_at(&mut redis_cache, path),
+            Err(ConfigError::InvalidEntry(..))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
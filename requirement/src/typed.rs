@@ -0,0 +1,309 @@
+use crate::{Requirement, RequirementError};
+use guild_common::{
+    Aggregation, BlockSpec, Chain, ChainParseError, EvmBalanceMetadata, EvmCallMetadata,
+    EvmLogsMetadata, FieldError, IdentityScope, Relation, RequirementType,
+    RequirementTypeParseError, Scalar, TokenType,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RequirementKind {
+    EvmBalance {
+        chain: Chain,
+        token: TokenType,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        block: Option<BlockSpec>,
+    },
+    EvmCall {
+        chain: Chain,
+        #[serde(flatten)]
+        call: EvmCallMetadata,
+    },
+    EvmLogs {
+        chain: Chain,
+        #[serde(flatten)]
+        logs: EvmLogsMetadata,
+    },
+    SolBalance {
+        chain: Chain,
+    },
+}
+
+impl RequirementKind {
+    pub fn typ(&self) -> RequirementType {
+        match self {
+            Self::EvmBalance { .. } => RequirementType::EvmBalance,
+            Self::EvmCall { .. } => RequirementType::EvmCall,
+            Self::EvmLogs { .. } => RequirementType::EvmLogs,
+            Self::SolBalance { .. } => RequirementType::SolBalance,
+        }
+    }
+
+    pub fn chain(&self) -> &Chain {
+        match self {
+            Self::EvmBalance { chain, .. }
+            | Self::EvmCall { chain, .. }
+            | Self::EvmLogs { chain, .. }
+            | Self::SolBalance { chain } => chain,
+        }
+    }
+
+    fn metadata(&self) -> String {
+        match self {
+            Self::EvmBalance { token, block, .. } => EvmBalanceMetadata {
+                token: token.clone(),
+                block: block.clone(),
+            }
+            .to_string(),
+            Self::EvmCall { call, .. } => call.to_string(),
+            Self::EvmLogs { logs, .. } => logs.to_string(),
+            Self::SolBalance { .. } => String::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TypedRequirement {
+    pub id: String,
+    #[serde(flatten)]
+    pub kind: RequirementKind,
+    pub relation: Relation<Scalar>,
+    #[serde(default)]
+    pub aggregation: Aggregation,
+    #[serde(default)]
+    pub identity_scope: IdentityScope,
+}
+
+impl From<TypedRequirement> for Requirement {
+    fn from(requirement: TypedRequirement) -> Self {
+        Self {
+            id: requirement.id,
+            typ: requirement.kind.typ().to_string(),
+            config_key: requirement.kind.chain().to_string(),
+            metadata: requirement.kind.metadata(),
+            relation: requirement.relation,
+            aggregation: requirement.aggregation,
+            identity_scope: requirement.identity_scope,
+        }
+    }
+}
+
+impl TryFrom<Requirement> for TypedRequirement {
+    type Error = RequirementError;
+
+    fn try_from(requirement: Requirement) -> Result<Self, Self::Error> {
+        let invalid = |field: &str, message: String| RequirementError::InvalidInput {
+            id: requirement.id.clone(),
+            errors: vec![FieldError::new(field, message)],
+            retryable: false,
+        };
+
+        let chain: Chain = requirement
+            .config_key
+            .parse()
+            .map_err(|err: ChainParseError| invalid("config_key", err.to_string()))?;
+
+        let typ: RequirementType = requirement
+            .typ
+            .parse()
+            .map_err(|err: RequirementTypeParseError| invalid("typ", err.to_string()))?;
+
+        let kind = match typ {
+            RequirementType::EvmBalance => {
+                let EvmBalanceMetadata { token, block } = requirement
+                    .metadata
+                    .parse()
+                    .map_err(|err: serde_json::Error| invalid("metadata", err.to_string()))?;
+                token
+                    .check_supported(typ)
+                    .map_err(|err| invalid("metadata", err.to_string()))?;
+
+                RequirementKind::EvmBalance {
+                    chain,
+                    token,
+                    block,
+                }
+            }
+            RequirementType::EvmCall => {
+                let call = requirement
+                    .metadata
+                    .parse()
+                    .map_err(|err: serde_json::Error| invalid("metadata", err.to_string()))?;
+
+                RequirementKind::EvmCall { chain, call }
+            }
+            RequirementType::EvmLogs => {
+                let logs = requirement
+                    .metadata
+                    .parse()
+                    .map_err(|err: serde_json::Error| invalid("metadata", err.to_string()))?;
+
+                RequirementKind::EvmLogs { chain, logs }
+            }
+            RequirementType::SolBalance => RequirementKind::SolBalance { chain },
+        };
+
+        Ok(Self {
+            id: requirement.id,
+            kind,
+            relation: requirement.relation,
+            aggregation: requirement.aggregation,
+            identity_scope: requirement.identity_scope,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Requirement, RequirementKind, TypedRequirement};
+    use guild_common::{
+        Aggregation, Chain, ContractAddress, IdentityScope, Relation, Scalar, TokenType,
+    };
+
+    #[test]
+    fn typed_requirement_round_trip() {
+        let typed = TypedRequirement {
+            id: "69".to_string(),
+            kind: RequirementKind::EvmBalance {
+                chain: Chain::Ethereum,
+                token: TokenType::Erc20 {
+                    address: ContractAddress::new("0x458691c1692cd82facfb2c5127e36d63213448a8")
+                        .unwrap(),
+                },
+                block: None,
+            },
+            relation: Relation::GreaterThan(Scalar::from(0)),
+            aggregation: Aggregation::Sum,
+            identity_scope: IdentityScope::Primary,
+        };
+
+        let requirement = Requirement::from(typed.clone());
+
+        assert_eq!(requirement.typ, "evm_balance");
+        assert_eq!(requirement.config_key, "ethereum");
+        assert_eq!(
+            requirement.metadata,
+            r#"{"Erc20":{"address":"0x458691c1692cd82facfb2c5127e36d63213448a8"}}"#
+        );
+        assert_eq!(TypedRequirement::try_from(requirement).unwrap(), typed);
+
+        let json = r#"{
+            "id": "70",
+            "type": "evm_balance",
+            "chain": "ethereum",
+            "token": "Native",
+            "block": { "Number": 17000000 },
+            "relation": { "GreaterOrEqualTo": 100.0 }
+        }"#;
+
+        let typed: TypedRequirement = serde_json::from_str(json).unwrap();
+        let requirement = Requirement::from(typed.clone());
+
+        assert_eq!(
+            requirement.metadata,
+            r#"{"Native":null,"block":{"Number":17000000}}"#
+        );
+        assert_eq!(TypedRequirement::try_from(requirement).unwrap(), typed);
+
+        let json = r#"{
+            "id": "71",
+            "type": "evm_call",
+            "chain": "ethereum",
+            "address": "0x9c4b82c5b7e50b4d6ac7bd9e0fda2ebd2c00b2e3",
+            "signature": "stakedBalance(address)",
+            "args": ["{user}"],
+            "returns": "Uint",
+            "decimals": 18,
+            "relation": { "GreaterOrEqualTo": 32.0 }
+        }"#;
+
+        let typed: TypedRequirement = serde_json::from_str(json).unwrap();
+        let requirement = Requirement::from(typed.clone());
+
+        assert_eq!(requirement.typ, "evm_call");
+        assert_eq!(
+            requirement.metadata,
+            r#"{"address":"0x9c4b82c5b7e50b4d6ac7bd9e0fda2ebd2c00b2e3","signature":"stakedBalance(address)","args":["{user}"],"returns":"Uint","decimals":18}"#
+        );
+        assert_eq!(TypedRequirement::try_from(requirement).unwrap(), typed);
+
+        let json = r#"{
+            "id": "72",
+            "type": "evm_logs",
+            "chain": "ethereum",
+            "address": "0x57f1887a8bf19b14fc0df6fd9b2acc9af147ea85",
+            "metric": "HoldingDuration",
+            "relation": { "GreaterOrEqualTo": 2592000.0 }
+        }"#;
+
+        let typed: TypedRequirement = serde_json::from_str(json).unwrap();
+        let requirement = Requirement::from(typed.clone());
+
+        assert_eq!(requirement.typ, "evm_logs");
+        assert_eq!(
+            requirement.metadata,
+            r#"{"address":"0x57f1887a8bf19b14fc0df6fd9b2acc9af147ea85","metric":"HoldingDuration","from_block":0}"#
+        );
+        assert_eq!(TypedRequirement::try_from(requirement).unwrap(), typed);
+
+        let json = r#"{
+            "id": "99",
+            "type": "sol_balance",
+            "chain": "solana_main",
+            "relation": { "GreaterThan": 420.0 }
+        }"#;
+
+        let typed: TypedRequirement = serde_json::from_str(json).unwrap();
+
+        assert_eq!(
+            typed.kind,
+            RequirementKind::SolBalance {
+                chain: Chain::SolanaMain
+            }
+        );
+
+        let requirement = Requirement::from(typed);
+
+        assert_eq!(requirement.typ, "sol_balance");
+        assert_eq!(requirement.config_key, "solana_main");
+        assert!(requirement.metadata.is_empty());
+        assert_eq!(requirement.aggregation, Aggregation::Any);
+        assert_eq!(requirement.identity_scope, IdentityScope::All);
+    }
+
+    #[test]
+    fn invalid_wire_requirement() {
+        let requirement = Requirement {
+            id: "1".to_string(),
+            typ: "evm_balance".to_string(),
+            config_key: "ethereum".to_string(),
+            metadata: "{}".to_string(),
+            relation: Relation::GreaterThan(Scalar::from(0)),
+            aggregation: Aggregation::default(),
+            identity_scope: IdentityScope::default(),
+        };
+
+        assert!(TypedRequirement::try_from(requirement).is_err());
+
+        let requirement = Requirement {
+            id: "2".to_string(),
+            typ: "evm_balance".to_string(),
+            config_key: "ethereum".to_string(),
+            metadata: r#"{"SplToken":{"mint":"EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"}}"#
+                .to_string(),
+            relation: Relation::GreaterThan(Scalar::from(0)),
+            aggregation: Aggregation::default(),
+            identity_scope: IdentityScope::default(),
+        };
+
+        assert_eq!(
+            TypedRequirement::try_from(requirement)
+                .unwrap_err()
+                .to_string(),
+            "Requirement 2: invalid input: metadata: SPL tokens are not supported by evm_balance requirements"
+        );
+    }
+}